@@ -117,8 +117,22 @@ _bbbbbbbbbb_`,
 let layout_select = document.getElementById("layout_select")
 let pieces_ta = document.getElementById("pieces_ta")
 let promotions_ta = document.getElementById("promotions_ta")
+let strict_checkbox = document.getElementById("strict_checkbox")
+let layout_warnings = document.getElementById("layout_warnings")
 let bottom_side_select = document.getElementById("bottom_side_select")
+let animation_speed_select = document.getElementById("animation_speed_select")
+let white_player_select = document.getElementById("white_player_select")
+let black_player_select = document.getElementById("black_player_select")
 let restart_button = document.getElementById("restart_button")
+let position_input = document.getElementById("position_input")
+let fen_input = document.getElementById("fen_input")
+let export_button = document.getElementById("export_button")
+let history_move_number = document.getElementById("history_move_number")
+let history_line_length = document.getElementById("history_line_length")
+let past_position_warning = document.getElementById("past_position_warning")
+let game_ta = document.getElementById("game_ta")
+let game_export_button = document.getElementById("game_export_button")
+let game_import_button = document.getElementById("game_import_button")
 
 layout_select.onchange = function() {
     let selected = layout_select.value
@@ -140,6 +154,28 @@ restart_button.onclick = function() {
     queued_restart = true;
 }
 
+let queued_export = false;
+export_button.onclick = function() {
+    queued_export = true;
+}
+
+let queued_game_export = false;
+game_export_button.onclick = function() {
+    queued_game_export = true;
+}
+
+let queued_game_import = false;
+game_import_button.onclick = function() {
+    queued_game_import = true;
+}
+
+let queued_history_command = "";
+for (let command of ["start", "back", "forward", "end", "undo", "redo"]) {
+    document.getElementById("history_" + command + "_button").onclick = function() {
+        queued_history_command = command;
+    }
+}
+
 function poll_restart() {
     if (queued_restart) {
         queued_restart = false;
@@ -148,6 +184,36 @@ function poll_restart() {
     return false;
 }
 
+function poll_export() {
+    if (queued_export) {
+        queued_export = false;
+        return true;
+    }
+    return false;
+}
+
+function poll_game_export() {
+    if (queued_game_export) {
+        queued_game_export = false;
+        return true;
+    }
+    return false;
+}
+
+function poll_game_import() {
+    if (queued_game_import) {
+        queued_game_import = false;
+        return true;
+    }
+    return false;
+}
+
+function poll_history_command() {
+    let command = queued_history_command;
+    queued_history_command = "";
+    return command;
+}
+
 function get_pieces_string() {
     return pieces_ta.value;
 }
@@ -156,6 +222,55 @@ function get_promotions_string() {
     return promotions_ta.value;
 }
 
+function get_strict_layout() {
+    return strict_checkbox.checked;
+}
+
 function get_bottom_side() {
     return bottom_side_select.value;
-}
\ No newline at end of file
+}
+
+function get_animation_speed() {
+    return animation_speed_select.value;
+}
+
+function get_white_player() {
+    return white_player_select.value;
+}
+
+function get_black_player() {
+    return black_player_select.value;
+}
+
+function set_position_notation(notation) {
+    position_input.value = notation;
+}
+
+function set_position_fen(fen) {
+    fen_input.value = fen;
+}
+
+function set_layout_warnings(warnings) {
+    layout_warnings.textContent = warnings;
+    layout_warnings.hidden = warnings == "";
+}
+
+function set_layout_strings(pieces_string, promotions_string) {
+    layout_select.value = "custom"
+    pieces_ta.value = pieces["custom"] = pieces_string
+    promotions_ta.value = promotions["custom"] = promotions_string
+}
+
+function set_history_position(move_number, line_length) {
+    history_move_number.textContent = move_number;
+    history_line_length.textContent = line_length;
+    past_position_warning.hidden = move_number == line_length;
+}
+
+function get_game_record() {
+    return game_ta.value;
+}
+
+function set_game_record(record) {
+    game_ta.value = record;
+}
@@ -0,0 +1,466 @@
+use crate::board::PieceModel::*;
+use crate::board::Space::*;
+use crate::board::*;
+use crate::logic::*;
+use std::collections::HashMap;
+
+// Scores are in centipawns, from the point of view of the side to play
+pub const MATE_SCORE: i32 = 1_000_000;
+const MOBILITY_WEIGHT: i32 = 4;
+// Deepest ply a search can reach, for telling mate scores apart
+const MAX_PLY: i32 = 1000;
+// Move ordering keys, above those given by the history heuristic
+const KILLER_ORDER: i32 = 1 << 20;
+const CAPTURE_ORDER: i32 = 1 << 21;
+// Attacker value of kings when ordering captures, which have no material value but are the riskiest to capture with
+const KING_ATTACKER_VALUE: i32 = 1000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SearchLimits {
+    pub max_depth: u32,
+    // The search stops at the first node past this count, keeping the best move found so far
+    pub max_nodes: u64,
+    // Number of transposition table entries, rounded down to a power of two
+    pub table_size: usize,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        SearchLimits {
+            max_depth: 32,
+            max_nodes: 200_000,
+            table_size: 1 << 16,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SearchResult {
+    pub best_move: Move,
+    pub score: i32,
+    pub depth: u32,
+    pub nodes: u64,
+}
+
+pub fn piece_value(model: PieceModel) -> i32 {
+    match model {
+        King { .. } => 0,
+        Queen => 900,
+        Bishop => 320,
+        Knight => 300,
+        Rook { .. } => 500,
+        Pawn { .. } => 100,
+    }
+}
+
+// Number of squares a piece could move to or capture on, ignoring checks
+// Cheaper than generating moves, which is all the evaluation needs
+fn piece_mobility(board: &Board, coords: Coords, piece: Piece) -> i32 {
+    let reachable = |target: Coords| match board.spaces.get(target) {
+        Some(Square { slot: None, .. }) => true,
+        Some(Square { slot: Some(other), .. }) => other.side != piece.side,
+        _ => false,
+    };
+    let steps = |offsets: &[[isize; 2]]| {
+        offsets
+            .iter()
+            .filter(|[dx, dy]| reachable(Coords { x: coords.x + dx, y: coords.y + dy }))
+            .count() as i32
+    };
+    let slides = |directions: &[[isize; 2]]| {
+        directions
+            .iter()
+            .map(|[dx, dy]| {
+                let mut count = 0;
+                let mut target = coords;
+                loop {
+                    target = Coords { x: target.x + dx, y: target.y + dy };
+                    match board.spaces.get(target) {
+                        Some(Square { slot: None, .. }) => count += 1,
+                        Some(Square { slot: Some(other), .. }) => {
+                            if other.side != piece.side {
+                                count += 1;
+                            }
+                            break count;
+                        }
+                        _ => break count,
+                    }
+                }
+            })
+            .sum::<i32>()
+    };
+
+    const ORTHOGONAL: [[isize; 2]; 4] = [[-1, 0], [0, 1], [0, -1], [1, 0]];
+    const DIAGONAL: [[isize; 2]; 4] = [[-1, 1], [1, 1], [-1, -1], [1, -1]];
+
+    match piece.model {
+        King { .. } => steps(&[ORTHOGONAL, DIAGONAL].concat()),
+        Queen => slides(&ORTHOGONAL) + slides(&DIAGONAL),
+        Bishop => slides(&DIAGONAL),
+        Rook { .. } => slides(&ORTHOGONAL),
+        Knight => steps(&[[1, 2], [2, 1], [2, -1], [1, -2], [-1, -2], [-2, -1], [-2, 1], [-1, 2]]),
+        Pawn { .. } => {
+            let y_dir = if piece.side == Side::White { 1 } else { -1 };
+            let forward = Coords { x: coords.x, y: coords.y + y_dir };
+            i32::from(matches!(board.spaces.get(forward), Some(Square { slot: None, .. })))
+        }
+    }
+}
+
+// Material and mobility balance, from the point of view of the side to play
+pub fn evaluate(board: &Board) -> i32 {
+    board
+        .spaces
+        .indexed_iter()
+        .map(|((x, y), space)| match space {
+            Square { slot: Some(piece), .. } => {
+                let coords = Coords { x: x as isize, y: y as isize };
+                let score = piece_value(piece.model) + MOBILITY_WEIGHT * piece_mobility(board, coords, *piece);
+                if piece.side == board.side { score } else { -score }
+            }
+            _ => 0,
+        })
+        .sum()
+}
+
+// Whether a score is a forced mate, whose distance to the root must be kept consistent in the transposition table
+fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE_SCORE - MAX_PLY
+}
+
+// Mate scores are stored relative to the node rather than to the root
+fn score_to_table(score: i32, ply: i32) -> i32 {
+    if is_mate_score(score) { score + ply * score.signum() } else { score }
+}
+
+fn score_from_table(score: i32, ply: i32) -> i32 {
+    if is_mate_score(score) { score - ply * score.signum() } else { score }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Bound {
+    Exact,
+    // The score is at least the stored one, the search failed high
+    Lower,
+    // The score is at most the stored one, no move beat alpha
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TableEntry {
+    hash: u64,
+    depth: u32,
+    score: i32,
+    bound: Bound,
+    best_move: Option<Move>,
+}
+
+// Fixed-size table of searched positions, indexed by the low bits of their hash
+// A new position always replaces an older one in its slot, a deeper search of the same position is kept
+struct TranspositionTable {
+    entries: Vec<Option<TableEntry>>,
+}
+
+impl TranspositionTable {
+    // The size is rounded down to a power of two
+    fn new(size: usize) -> Self {
+        let size = if size == 0 { 1 } else { 1 << size.ilog2() };
+        TranspositionTable { entries: vec![None; size] }
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        hash as usize & (self.entries.len() - 1)
+    }
+
+    // Number of slots holding a position
+    fn len(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.is_some()).count()
+    }
+
+    fn get(&self, hash: u64) -> Option<&TableEntry> {
+        self.entries[self.index(hash)].as_ref().filter(|entry| entry.hash == hash)
+    }
+
+    fn store(&mut self, entry: TableEntry) {
+        let index = self.index(entry.hash);
+        let slot = &mut self.entries[index];
+        if slot.is_none_or(|old| old.hash != entry.hash || old.depth <= entry.depth) {
+            *slot = Some(entry);
+        }
+    }
+}
+
+// Captures and promotions, which are searched before other moves
+fn is_quiet(move_: &Move) -> bool {
+    !matches!(move_.kind, MoveKind::Capture | MoveKind::EnPassant) && move_.promotion.is_none()
+}
+
+// Most valuable victim, least valuable attacker
+fn mvv_lva(board: &Board, move_: &Move) -> i32 {
+    let model_at = |coords: Coords| match board.spaces.get(coords) {
+        Some(Square { slot: Some(piece), .. }) => Some(piece.model),
+        _ => None,
+    };
+
+    let victim = match move_.kind {
+        MoveKind::EnPassant => piece_value(Pawn { can_dash: false, just_dashed: false }),
+        _ => model_at(move_.target).map_or(0, piece_value),
+    };
+    let attacker = match model_at(move_.source) {
+        Some(King { .. }) => KING_ATTACKER_VALUE,
+        model => model.map_or(0, piece_value),
+    };
+    10 * victim - attacker
+}
+
+struct Search {
+    limits: SearchLimits,
+    nodes: u64,
+    aborted: bool,
+    table: TranspositionTable,
+    // Quiet moves which caused a cutoff, by ply
+    killers: Vec<[Option<Move>; 2]>,
+    // Cutoffs caused by quiet moves, weighted by depth, by source and target
+    history: HashMap<(Coords, Coords), i32>,
+}
+
+impl Search {
+    fn new(limits: SearchLimits) -> Self {
+        Search {
+            limits,
+            nodes: 0,
+            aborted: false,
+            table: TranspositionTable::new(limits.table_size),
+            killers: vec![],
+            history: HashMap::new(),
+        }
+    }
+
+    // Table move first, then captures and promotions, killer moves, and other moves by history
+    fn order_moves(&self, board: &Board, moves: &mut [Move], ply: i32, table_move: Option<Move>) {
+        let killers = self.killers.get(ply as usize).copied().unwrap_or_default();
+
+        moves.sort_by_cached_key(|move_| {
+            let key = if Some(*move_) == table_move {
+                i32::MAX
+            } else if !is_quiet(move_) {
+                let capture = match move_.kind {
+                    MoveKind::Capture | MoveKind::EnPassant => mvv_lva(board, move_),
+                    _ => 0,
+                };
+                CAPTURE_ORDER + capture + move_.promotion.map_or(0, piece_value)
+            } else if killers.contains(&Some(*move_)) {
+                KILLER_ORDER
+            } else {
+                self.history.get(&(move_.source, move_.target)).copied().unwrap_or(0).min(KILLER_ORDER - 1)
+            };
+            std::cmp::Reverse(key)
+        });
+    }
+
+    fn record_cutoff(&mut self, move_: &Move, depth: u32, ply: i32) {
+        if !is_quiet(move_) {
+            return;
+        }
+
+        let ply = ply as usize;
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(*move_) {
+            killers[1] = killers[0];
+            killers[0] = Some(*move_);
+        }
+
+        *self.history.entry((move_.source, move_.target)).or_default() += (depth * depth) as i32;
+    }
+
+    fn negamax(&mut self, board: &mut Board, depth: u32, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.nodes > self.limits.max_nodes {
+            self.aborted = true;
+            return 0;
+        }
+
+        if depth == 0 {
+            return evaluate(board);
+        }
+
+        let original_alpha = alpha;
+        let mut table_move = None;
+        if let Some(entry) = self.table.get(board.hash) {
+            table_move = entry.best_move;
+            if entry.depth >= depth {
+                let score = score_from_table(entry.score, ply);
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return beta,
+                    Bound::Upper if score <= alpha => return alpha,
+                    _ => (),
+                }
+            }
+        }
+
+        let mut moves: Vec<Move> = compute_possible_moves(board, true).into_values().flatten().collect();
+        if moves.is_empty() {
+            // Prefer the quickest mates and the slowest defeats
+            return if is_in_check(board) { -MATE_SCORE + ply } else { 0 };
+        }
+        self.order_moves(board, &mut moves, ply, table_move);
+
+        let mut best_move = None;
+        for move_ in &moves {
+            let undo = make_move(board, move_);
+            let score = -self.negamax(board, depth - 1, ply + 1, -beta, -alpha);
+            unmake_move(board, undo);
+
+            if self.aborted {
+                return 0;
+            }
+            if score >= beta {
+                self.record_cutoff(move_, depth, ply);
+                self.table.store(TableEntry {
+                    hash: board.hash,
+                    depth,
+                    score: score_to_table(beta, ply),
+                    bound: Bound::Lower,
+                    best_move: Some(*move_),
+                });
+                return beta;
+            }
+            if score > alpha {
+                alpha = score;
+                best_move = Some(*move_);
+            }
+        }
+
+        self.table.store(TableEntry {
+            hash: board.hash,
+            depth,
+            score: score_to_table(alpha, ply),
+            bound: if alpha > original_alpha { Bound::Exact } else { Bound::Upper },
+            best_move,
+        });
+        alpha
+    }
+}
+
+// Iterative deepening alpha-beta search which can be run a few root moves at a time,
+// so that callers can spread it over several frames or yield between chunks
+pub struct Searcher {
+    board: Board,
+    root_moves: Vec<Move>,
+    search: Search,
+    result: SearchResult,
+    // Progress within the depth being searched
+    depth: u32,
+    root_index: usize,
+    alpha: i32,
+    best_index: usize,
+    finished: bool,
+}
+
+impl Searcher {
+    // Returns None if the side to play has no legal move
+    pub fn new(board: &Board, limits: SearchLimits) -> Option<Self> {
+        let search = Search::new(limits);
+        let mut root_moves: Vec<Move> = compute_possible_moves(board, true).into_values().flatten().collect();
+        search.order_moves(board, &mut root_moves, 0, None);
+        let first_move = *root_moves.first()?;
+
+        Some(Searcher {
+            board: board.clone(),
+            root_moves,
+            search,
+            result: SearchResult {
+                best_move: first_move,
+                score: 0,
+                depth: 0,
+                nodes: 0,
+            },
+            depth: 1,
+            root_index: 0,
+            alpha: -MATE_SCORE - 1,
+            best_index: 0,
+            finished: limits.max_depth == 0,
+        })
+    }
+
+    // Searches root moves until at least node_budget nodes were visited or the search is over
+    // Root moves are never split, so a step overshoots its budget by up to one root move's subtree
+    // Returns whether the search is over
+    pub fn step(&mut self, node_budget: u64) -> bool {
+        let start_nodes = self.search.nodes;
+
+        while !self.finished && self.search.nodes - start_nodes < node_budget {
+            let move_ = self.root_moves[self.root_index];
+            let undo = make_move(&mut self.board, &move_);
+            let score = -self.search.negamax(&mut self.board, self.depth - 1, 1, -MATE_SCORE - 1, -self.alpha);
+            unmake_move(&mut self.board, undo);
+
+            if self.search.aborted {
+                self.finished = true;
+                break;
+            }
+            if score > self.alpha {
+                self.alpha = score;
+                self.best_index = self.root_index;
+            }
+
+            self.root_index += 1;
+            if self.root_index == self.root_moves.len() {
+                self.complete_depth();
+            }
+        }
+
+        self.result.nodes = self.search.nodes;
+        self.finished
+    }
+
+    // Best move found so far
+    // The previous best move is searched first at each depth, so a better one found by a partial depth can be trusted
+    pub fn result(&self) -> SearchResult {
+        if self.root_index > 0 && self.best_index > 0 {
+            SearchResult {
+                best_move: self.root_moves[self.best_index],
+                score: self.alpha,
+                ..self.result
+            }
+        } else {
+            self.result
+        }
+    }
+
+    // Number of positions stored in the transposition table, which never exceeds its size
+    pub fn table_entries(&self) -> usize {
+        self.search.table.len()
+    }
+
+    fn complete_depth(&mut self) {
+        // Search the best move first at the next depth
+        self.root_moves[..=self.best_index].rotate_right(1);
+        self.result = SearchResult {
+            best_move: self.root_moves[0],
+            score: self.alpha,
+            depth: self.depth,
+            nodes: self.search.nodes,
+        };
+
+        // No need to look deeper once a forced mate is found
+        self.finished = is_mate_score(self.alpha) || self.depth == self.search.limits.max_depth;
+
+        self.depth += 1;
+        self.root_index = 0;
+        self.alpha = -MATE_SCORE - 1;
+        self.best_index = 0;
+    }
+}
+
+// Runs a whole search at once
+// Returns None if the side to play has no legal move
+pub fn search(board: &Board, limits: SearchLimits) -> Option<SearchResult> {
+    let mut searcher = Searcher::new(board, limits)?;
+    searcher.step(u64::MAX);
+    Some(searcher.result())
+}
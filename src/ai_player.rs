@@ -0,0 +1,116 @@
+use bevy::prelude::*;
+#[cfg(not(target_family = "wasm"))]
+use bevy::tasks::{block_on, futures_lite::future, poll_once, AsyncComputeTaskPool, Task};
+use bevy::utils::{Duration, Instant};
+use chess2::ai::*;
+use chess2::logic::*;
+use chess2::turns::*;
+
+// Nodes searched between two checks for cancellation, the end of a frame slice or of the thinking time
+const NODES_PER_CHUNK: u64 = 500;
+// Time after which the computer plays the best move found so far
+const THINKING_TIME: Duration = Duration::from_secs(2);
+// Time the search may take out of each frame on the web, where it shares the main thread
+#[cfg(target_family = "wasm")]
+const FRAME_SLICE: Duration = Duration::from_millis(8);
+
+pub struct AIPlugin;
+
+impl Plugin for AIPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AIPlayers::default())
+            .add_systems(Update, play_ai_move);
+    }
+}
+
+// Sides played by the computer, indexed by Side
+#[derive(Resource, Default)]
+pub struct AIPlayers {
+    pub sides: [bool; 2],
+    pub limits: SearchLimits,
+    thinking: Option<AIThinking>,
+}
+
+impl AIPlayers {
+    // Drops the search in progress, if any
+    pub fn cancel(&mut self) {
+        self.thinking = None;
+    }
+}
+
+// Search in progress for a turn of the game tree
+struct AIThinking {
+    turn: TurnId,
+    #[cfg(target_family = "wasm")]
+    started: Instant,
+    // Dropping the task cancels it the next time it yields
+    #[cfg(not(target_family = "wasm"))]
+    task: Task<SearchResult>,
+    #[cfg(target_family = "wasm")]
+    searcher: Searcher,
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl AIThinking {
+    fn start(turn: usize, mut searcher: Searcher) -> Self {
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            let started = Instant::now();
+            while !searcher.step(NODES_PER_CHUNK) && started.elapsed() < THINKING_TIME {
+                future::yield_now().await;
+            }
+            searcher.result()
+        });
+        AIThinking { turn, task }
+    }
+
+    // Returns the result of the search once it is over
+    fn poll(&mut self) -> Option<SearchResult> {
+        block_on(poll_once(&mut self.task))
+    }
+}
+
+#[cfg(target_family = "wasm")]
+impl AIThinking {
+    fn start(turn: usize, searcher: Searcher) -> Self {
+        AIThinking { turn, started: Instant::now(), searcher }
+    }
+
+    // Advances the search for one frame slice, and returns its result once it is over
+    fn poll(&mut self) -> Option<SearchResult> {
+        let start = Instant::now();
+        while start.elapsed() < FRAME_SLICE {
+            if self.searcher.step(NODES_PER_CHUNK) || self.started.elapsed() >= THINKING_TIME {
+                return Some(self.searcher.result());
+            }
+        }
+        None
+    }
+}
+
+fn play_ai_move(
+    mut ai_players: ResMut<AIPlayers>,
+    mut turns: ResMut<Turns>
+) {
+    let current_turn = turns.current_id();
+
+    // Only play at the end of a line, not while looking at turns that already have a continuation
+    let at_line_end = turns.children(current_turn).is_empty();
+    if ai_players.thinking.as_ref().is_some_and(|thinking| thinking.turn != current_turn || !at_line_end) {
+        ai_players.cancel();
+    }
+    if !at_line_end { return; }
+
+    if ai_players.thinking.is_none() {
+        let Some(Turn { board, outcome, .. }) = turns.current() else { return };
+        if *outcome != GameOutcome::Ongoing || !ai_players.sides[board.side as usize] { return; }
+
+        let Some(searcher) = Searcher::new(board, ai_players.limits) else { return };
+        ai_players.thinking = Some(AIThinking::start(current_turn, searcher));
+    }
+
+    let Some(thinking) = ai_players.thinking.as_mut() else { return };
+    let Some(result) = thinking.poll() else { return };
+    ai_players.cancel();
+
+    turns.push_move(&result.best_move);
+}
@@ -1,14 +1,21 @@
+#[cfg(feature = "bevy")]
 use bevy::prelude::*;
 use ndarray::*;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
 
+#[cfg(feature = "bevy")]
 pub struct BoardPlugin;
 
+#[cfg(feature = "bevy")]
 impl Plugin for BoardPlugin {
     fn build(&self, _app: &mut App) {}
 }
 
 // Signed coordinates, useful for computations before filtering out of bounds squares
-#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+#[cfg_attr(feature = "bevy", derive(Component))]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
 pub struct Coords {
     pub x: isize,
     pub y: isize,
@@ -38,7 +45,7 @@ pub enum Side {
 use Side::*;
 
 impl Side {
-    pub fn other(self: &Self) -> Self {
+    pub fn other(&self) -> Self {
         match self {
             White => Black,
             Black => White,
@@ -58,14 +65,15 @@ pub enum PieceModel {
 use PieceModel::*;
 
 // Marks an entity as that of a piece on the board.
-#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "bevy", derive(Component))]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Piece {
     pub side: Side,
     pub model: PieceModel,
 }
 
 impl Piece {
-    pub fn texture_index(self: &Self) -> usize {
+    pub fn texture_index(&self) -> usize {
         (match self.model {
             King { .. } => 0,
             Queen => 1,
@@ -75,6 +83,43 @@ impl Piece {
             Pawn { .. } => 5,
         }) + if self.side == Black { 6 } else { 0 }
     }
+
+    // Piece from its layout letter (uppercase for white), in its initial state
+    pub fn from_symbol(symbol: char) -> Option<Self> {
+        let model = match symbol.to_ascii_uppercase() {
+            'K' => King { can_castle: true },
+            'Q' => Queen,
+            'B' => Bishop,
+            'N' => Knight,
+            'R' => Rook { can_castle: true },
+            'P' => Pawn {
+                can_dash: true,
+                just_dashed: false,
+            },
+            _ => return None,
+        };
+
+        Some(Piece {
+            side: if symbol.is_ascii_uppercase() { White } else { Black },
+            model,
+        })
+    }
+
+    pub fn symbol(&self) -> char {
+        let symbol = match self.model {
+            King { .. } => 'K',
+            Queen => 'Q',
+            Bishop => 'B',
+            Knight => 'N',
+            Rook { .. } => 'R',
+            Pawn { .. } => 'P',
+        };
+
+        match self.side {
+            White => symbol,
+            Black => symbol.to_ascii_lowercase(),
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
@@ -88,128 +133,769 @@ pub enum Space {
 }
 use Space::*;
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LayoutString {
+    Pieces,
+    Promotions,
+}
+
+// Errors of Board::from_strings
+// Rows and columns are the 1-based line and character positions in the offending string
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LayoutError {
+    Empty,
+    RowCountMismatch { pieces_rows: usize, promotions_rows: usize },
+    RowWidthMismatch { string: LayoutString, row: usize, expected: usize, found: usize },
+    InvalidSymbol { string: LayoutString, row: usize, column: usize, symbol: char },
+}
+
+impl fmt::Display for LayoutString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayoutString::Pieces => write!(f, "Pieces"),
+            LayoutString::Promotions => write!(f, "Promotions"),
+        }
+    }
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayoutError::Empty => write!(f, "Board strings are empty"),
+            LayoutError::RowCountMismatch { pieces_rows, promotions_rows } => write!(
+                f,
+                "Board strings have different row counts: {} pieces rows, {} promotions rows",
+                pieces_rows, promotions_rows
+            ),
+            LayoutError::RowWidthMismatch { string, row, expected, found } => write!(
+                f,
+                "{} string, row {}: {} squares instead of {}",
+                string, row, found, expected
+            ),
+            LayoutError::InvalidSymbol { string, row, column, symbol } => write!(
+                f,
+                "{} string, row {}, column {}: unknown symbol '{}'",
+                string, row, column, symbol
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+// Non-empty line of a layout string, with its position in that string
+struct LayoutRow {
+    line: usize,
+    first_column: usize,
+    symbols: Vec<char>,
+}
+
+fn layout_rows(string: &str) -> Vec<LayoutRow> {
+    string
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| LayoutRow {
+            line: index + 1,
+            first_column: line.chars().take_while(|c| c.is_whitespace()).count() + 1,
+            symbols: line.trim().chars().collect(),
+        })
+        .collect()
+}
+
+// Sides allowed to promote on a square, from its promotion layout symbol
+fn promotes_from_symbol(symbol: char) -> Option<[bool; 2]> {
+    match symbol {
+        'P' | 'w' | 'W' => Some([true, false]),
+        'p' | 'b' | 'B' => Some([false, true]),
+        '*' => Some([true, true]),
+        _ => None,
+    }
+}
+
+fn promotes_symbol(promotes: [bool; 2]) -> Option<char> {
+    match promotes {
+        [true, false] => Some('W'),
+        [false, true] => Some('b'),
+        [true, true] => Some('*'),
+        [false, false] => None,
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Default, Debug)]
 pub struct Board {
     pub spaces: Array2<Space>,
     pub side: Side,
     pub captured: Vec<Piece>,
+    // Zobrist hash of the pieces on the board and the side to move, see compute_hash
+    // Kept up to date by make_move, must be recomputed after editing spaces or side directly
+    pub hash: u64,
+}
+
+// Width and height of boards that FEN can describe
+const FEN_SIZE: usize = 8;
+// Widest row accepted in position notation, bounding the runs of its first row, which sets the width of the others
+const MAX_NOTATION_WIDTH: usize = 1024;
+// Most rows accepted in position notation, in its squares and in its promotions
+const MAX_NOTATION_HEIGHT: usize = 1024;
+
+// Zobrist key toggled when Black is to move
+pub const BLACK_TO_MOVE_KEY: u64 = 0x9e37_79b9_7f4a_7c15;
+
+// SplitMix64 finalizer, spreading any input over all 64 bits
+fn mix_bits(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+// Zobrist key of the contents of a square, 0 if it is empty
+// Keys are derived from the coordinates rather than read from a table, so they fit boards of any size
+pub fn slot_key(coords: Coords, slot: Option<Piece>) -> u64 {
+    let Some(piece) = slot else { return 0 };
+
+    let state = match piece.model {
+        King { can_castle } => u64::from(can_castle),
+        Queen => 2,
+        Bishop => 3,
+        Knight => 4,
+        Rook { can_castle } => 5 + u64::from(can_castle),
+        Pawn { can_dash, just_dashed } => 7 + u64::from(can_dash) + 2 * u64::from(just_dashed),
+    } + 16 * piece.side as u64;
+
+    mix_bits(mix_bits(coords.x as u64) ^ mix_bits(((coords.y as u64) << 32) | state))
 }
 
 impl Board {
-    pub fn from_strings<'a>(board_string: &'a str, promotion_string: &'a str) -> Result<Self, &'static str> {
-        let get_byte_rows = |s: &'a str| {
-            s.lines()
-                .rev()
-                .map(|row| row.trim())
-                .filter(|row| !row.is_empty())
-                .map(|row| row.as_bytes())
+    // Full computation of the Zobrist hash, which make_move then updates incrementally
+    pub fn compute_hash(&self) -> u64 {
+        let pieces_hash = self
+            .spaces
+            .indexed_iter()
+            .map(|((x, y), space)| match space {
+                Square { slot, .. } => slot_key(Coords { x: x as isize, y: y as isize }, *slot),
+                Hole => 0,
+            })
+            .fold(0, |hash, key| hash ^ key);
+
+        match self.side {
+            White => pieces_hash,
+            Black => pieces_hash ^ BLACK_TO_MOVE_KEY,
+        }
+    }
+
+    // Parses a board layout, unknown symbols being read as empty squares without promotions
+    pub fn from_strings(pieces_string: &str, promotions_string: &str) -> Result<Self, LayoutError> {
+        Self::parse_strings(pieces_string, promotions_string, false)
+    }
+
+    // Parses a board layout, rejecting any unknown symbol
+    pub fn from_strings_strict(pieces_string: &str, promotions_string: &str) -> Result<Self, LayoutError> {
+        Self::parse_strings(pieces_string, promotions_string, true)
+    }
+
+    fn parse_strings(pieces_string: &str, promotions_string: &str, strict: bool) -> Result<Self, LayoutError> {
+        let (pieces_rows, promotions_rows) = (layout_rows(pieces_string), layout_rows(promotions_string));
+
+        let Some(first_row) = pieces_rows.first() else {
+            return Err(LayoutError::Empty);
         };
+        let (bw, bh) = (first_row.symbols.len(), pieces_rows.len());
+
+        if promotions_rows.len() != bh {
+            return Err(LayoutError::RowCountMismatch {
+                pieces_rows: bh,
+                promotions_rows: promotions_rows.len(),
+            });
+        }
 
-        if !(board_string.is_ascii() && promotion_string.is_ascii()) {
-            return Err("Non-ASCII characters in board strings");
+        for (string, rows) in [(LayoutString::Pieces, &pieces_rows), (LayoutString::Promotions, &promotions_rows)] {
+            if let Some(row) = rows.iter().find(|row| row.symbols.len() != bw) {
+                return Err(LayoutError::RowWidthMismatch {
+                    string,
+                    row: row.line,
+                    expected: bw,
+                    found: row.symbols.len(),
+                });
+            }
         }
-        let (b_rows, p_rows) = (get_byte_rows(board_string), get_byte_rows(promotion_string));
-        let bh = b_rows.clone().count();
 
-        if bh != p_rows.clone().count() {
-            return Err("Board strings have different row counts");
+        let invalid_symbol = |string, row: &LayoutRow, index: usize| LayoutError::InvalidSymbol {
+            string,
+            row: row.line,
+            column: row.first_column + index,
+            symbol: row.symbols[index],
+        };
+
+        // Rows are parsed from top to bottom
+        let mut spaces_rows = Vec::with_capacity(bh);
+        for (pieces_row, promotions_row) in pieces_rows.iter().zip(&promotions_rows) {
+            let mut spaces_row = Vec::with_capacity(bw);
+
+            for index in 0..bw {
+                let (piece_symbol, promotion_symbol) = (pieces_row.symbols[index], promotions_row.symbols[index]);
+
+                let promotes = match promotes_from_symbol(promotion_symbol) {
+                    Some(promotes) => promotes,
+                    None if matches!(promotion_symbol, '_' | 'X') => [false, false],
+                    None if strict || !promotion_symbol.is_ascii() => {
+                        return Err(invalid_symbol(LayoutString::Promotions, promotions_row, index));
+                    }
+                    None => [false, false],
+                };
+
+                spaces_row.push(match piece_symbol {
+                    'X' => Hole,
+                    '_' => Square { slot: None, promotes },
+                    _ => match Piece::from_symbol(piece_symbol) {
+                        Some(piece) => Square { slot: Some(piece), promotes },
+                        None if strict || !piece_symbol.is_ascii() => {
+                            return Err(invalid_symbol(LayoutString::Pieces, pieces_row, index));
+                        }
+                        None => Square { slot: None, promotes },
+                    },
+                });
+            }
+
+            spaces_rows.push(spaces_row);
+        }
+
+        let mut board = Board {
+            spaces: Array2::from_shape_fn((bw, bh), |(x, y)| spaces_rows[bh - 1 - y][x]),
+            captured: vec![],
+            side: White,
+            hash: 0,
+        };
+        board.hash = board.compute_hash();
+        Ok(board)
+    }
+
+    // Exports the board as the pieces and promotions strings read by from_strings
+    // Piece states (castling and dashing rights), the side to move and captured pieces are not part of that format
+    pub fn to_strings(&self) -> (String, String) {
+        let (bw, bh) = self.spaces.dim();
+
+        let mut pieces_rows = vec![];
+        let mut promotions_rows = vec![];
+        for y in (0..bh).rev() {
+            let (mut pieces_row, mut promotions_row) = (String::new(), String::new());
+
+            for x in 0..bw {
+                match self.spaces[(x, y)] {
+                    Hole => {
+                        pieces_row.push('X');
+                        promotions_row.push('X');
+                    }
+                    Square { slot, promotes } => {
+                        pieces_row.push(slot.map_or('_', |piece| piece.symbol()));
+                        promotions_row.push(promotes_symbol(promotes).unwrap_or('_'));
+                    }
+                }
+            }
+
+            pieces_rows.push(pieces_row);
+            promotions_rows.push(promotions_row);
+        }
+
+        (pieces_rows.join("\n"), promotions_rows.join("\n"))
+    }
+
+    // Single-line, lossless position notation, generalizing FEN to arbitrary boards:
+    // "<squares> <promotions> <side> <captured>", e.g. for the standard starting position
+    // "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR WWWWWWWW/8/8/8/8/8/8/bbbbbbbb w -"
+    // - squares: rows from top to bottom separated by '/', made of piece letters, X for holes
+    //   and numbers for runs of empty squares. Pieces may be followed by state markers:
+    //   ' for kings and rooks that can no longer castle or pawns that can no longer dash, ! for pawns that just dashed
+    // - promotions: rows laid out the same way, W, b or * for squares where white, black or both sides promote,
+    //   and numbers for runs of other squares or holes
+    // - side: w or b
+    // - captured: captured pieces with their markers, or - if there are none
+    pub fn to_notation(&self) -> String {
+        let (bw, bh) = self.spaces.dim();
+
+        let mut squares = vec![];
+        let mut promotions = vec![];
+        for y in (0..bh).rev() {
+            let (mut squares_row, mut promotions_row) = (String::new(), String::new());
+            let (mut empty_run, mut promotion_run) = (0, 0);
+
+            for x in 0..bw {
+                let space = self.spaces[(x, y)];
+
+                match space {
+                    Square { slot: None, .. } => empty_run += 1,
+                    _ => {
+                        if empty_run > 0 {
+                            squares_row.push_str(&empty_run.to_string());
+                            empty_run = 0;
+                        }
+                        match space {
+                            Square { slot: Some(piece), .. } => squares_row.push_str(&piece_notation(&piece)),
+                            _ => squares_row.push('X'),
+                        }
+                    }
+                }
+
+                match space {
+                    Square { promotes, .. } if promotes != [false, false] => {
+                        if promotion_run > 0 {
+                            promotions_row.push_str(&promotion_run.to_string());
+                            promotion_run = 0;
+                        }
+                        promotions_row.extend(promotes_symbol(promotes));
+                    }
+                    _ => promotion_run += 1,
+                }
+            }
+
+            if empty_run > 0 {
+                squares_row.push_str(&empty_run.to_string());
+            }
+            if promotion_run > 0 {
+                promotions_row.push_str(&promotion_run.to_string());
+            }
+            squares.push(squares_row);
+            promotions.push(promotions_row);
         }
-            
-        let bw = if let Some(first_row) = b_rows.clone().next() {
-            first_row.len()
+
+        let captured = if self.captured.is_empty() {
+            String::from("-")
         } else {
-            return Err("Board strings are empty");
+            self.captured.iter().map(piece_notation).collect()
+        };
+
+        format!(
+            "{} {} {} {}",
+            squares.join("/"),
+            promotions.join("/"),
+            match self.side {
+                White => 'w',
+                Black => 'b',
+            },
+            captured
+        )
+    }
+
+    // Parses the notation produced by to_notation
+    pub fn from_notation(notation: &str) -> Result<Self, &'static str> {
+        let [squares, promotions, side, captured] = notation.split_whitespace().collect::<Vec<_>>()[..] else {
+            return Err("Position notation must have 4 space-separated fields");
         };
 
-        let rows = b_rows.zip(p_rows);
-        if !rows.clone().all(
-            |(b_row, p_row)| b_row.len() == bw && b_row.len() == p_row.len()
-        ) {
-            return Err("Inconsistent row sizes across board strings");
-        }
-
-        Ok(Board {
-            spaces: Array2::from_shape_vec(
-                (bh, bw),
-                rows.flat_map(|(b_row, p_row)| {
-                    b_row
-                        .iter()
-                        .zip(p_row.iter())
-                        .map(|(&square, &prom)| match square {
-                            b'X' => Hole,
-                            square => Square {
-                                slot: match square {
-                                    b'K' => Some(Piece {
-                                        side: White,
-                                        model: King { can_castle: true },
-                                    }),
-                                    b'Q' => Some(Piece {
-                                        side: White,
-                                        model: Queen,
-                                    }),
-                                    b'R' => Some(Piece {
-                                        side: White,
-                                        model: Rook { can_castle: true },
-                                    }),
-                                    b'B' => Some(Piece {
-                                        side: White,
-                                        model: Bishop,
-                                    }),
-                                    b'N' => Some(Piece {
-                                        side: White,
-                                        model: Knight,
-                                    }),
-                                    b'P' => Some(Piece {
-                                        side: White,
-                                        model: Pawn {
-                                            can_dash: true,
-                                            just_dashed: false,
-                                        },
-                                    }),
-                                    b'k' => Some(Piece {
-                                        side: Black,
-                                        model: King { can_castle: true },
-                                    }),
-                                    b'q' => Some(Piece {
-                                        side: Black,
-                                        model: Queen,
-                                    }),
-                                    b'r' => Some(Piece {
-                                        side: Black,
-                                        model: Rook { can_castle: true },
-                                    }),
-                                    b'b' => Some(Piece {
-                                        side: Black,
-                                        model: Bishop,
-                                    }),
-                                    b'n' => Some(Piece {
-                                        side: Black,
-                                        model: Knight,
-                                    }),
-                                    b'p' => Some(Piece {
-                                        side: Black,
-                                        model: Pawn {
-                                            can_dash: true,
-                                            just_dashed: false,
-                                        },
-                                    }),
-                                    _ => None,
-                                },
-                                promotes: match prom {
-                                    b'P' | b'w' | b'W' => [true, false],
-                                    b'p' | b'b' | b'B' => [false, true],
-                                    b'*' => [true, true],
-                                    _ => [false, false],
-                                },
-                            },
+        // Rows are counted before being parsed, so that no huge board is ever allocated
+        if squares.split('/').count() > MAX_NOTATION_HEIGHT || promotions.split('/').count() > MAX_NOTATION_HEIGHT {
+            return Err("Too many rows in position notation");
+        }
+
+        // Runs can't go past the width of the first row, so that no huge row is ever allocated
+        let mut max_width = MAX_NOTATION_WIDTH;
+        let square_rows = squares
+            .split('/')
+            .map(|row| {
+                let mut spaces = vec![];
+                let mut symbols = row.chars().peekable();
+                while let Some(symbol) = symbols.next() {
+                    if symbol.is_ascii_digit() {
+                        let run = parse_run(symbol, &mut symbols, max_width.saturating_sub(spaces.len()))?;
+                        spaces.extend(std::iter::repeat_n(Square { slot: None, promotes: [false, false] }, run));
+                    } else if symbol == 'X' {
+                        spaces.push(Hole);
+                    } else {
+                        spaces.push(Square {
+                            slot: Some(parse_piece_notation(symbol, &mut symbols)?),
+                            promotes: [false, false],
+                        });
+                    }
+                }
+                max_width = max_width.min(spaces.len());
+                Ok(spaces)
+            })
+            .collect::<Result<Vec<_>, &'static str>>()?;
+
+        let promotion_rows = promotions
+            .split('/')
+            .map(|row| {
+                let mut row_promotes = vec![];
+                let mut symbols = row.chars().peekable();
+                while let Some(symbol) = symbols.next() {
+                    if symbol.is_ascii_digit() {
+                        let run = parse_run(symbol, &mut symbols, max_width.saturating_sub(row_promotes.len()))?;
+                        row_promotes.extend(std::iter::repeat_n([false, false], run));
+                    } else {
+                        row_promotes.push(promotes_from_symbol(symbol).ok_or("Invalid promotion symbol in position notation")?);
+                    }
+                }
+                Ok(row_promotes)
+            })
+            .collect::<Result<Vec<_>, &'static str>>()?;
+
+        let bh = square_rows.len();
+        let bw = square_rows[0].len();
+        if bw == 0 {
+            return Err("Empty board in position notation");
+        }
+        if promotion_rows.len() != bh {
+            return Err("Squares and promotions have different row counts in position notation");
+        }
+        if !square_rows.iter().zip(&promotion_rows).all(|(s_row, p_row)| s_row.len() == bw && p_row.len() == bw) {
+            return Err("Inconsistent row sizes in position notation");
+        }
+
+        let mut spaces = Array2::from_shape_fn((bw, bh), |(x, y)| square_rows[bh - 1 - y][x]);
+        for ((x, y), space) in spaces.indexed_iter_mut() {
+            let row_promotes = promotion_rows[bh - 1 - y][x];
+            match space {
+                Square { promotes, .. } => *promotes = row_promotes,
+                Hole if row_promotes != [false, false] => return Err("Promotion on a hole in position notation"),
+                Hole => (),
+            }
+        }
+
+        let side = match side {
+            "w" => White,
+            "b" => Black,
+            _ => return Err("Invalid side in position notation"),
+        };
+
+        let mut captured_pieces = vec![];
+        if captured != "-" {
+            let mut symbols = captured.chars().peekable();
+            while let Some(symbol) = symbols.next() {
+                captured_pieces.push(parse_piece_notation(symbol, &mut symbols)?);
+            }
+        }
+
+        let mut board = Board {
+            spaces,
+            side,
+            captured: captured_pieces,
+            hash: 0,
+        };
+        board.hash = board.compute_hash();
+        Ok(board)
+    }
+
+    // Parses a standard FEN record, see from_fen_with_clocks
+    pub fn from_fen(fen: &str) -> Result<Self, &'static str> {
+        Self::from_fen_with_clocks(fen).map(|(board, ..)| board)
+    }
+
+    // Parses a standard FEN record into an 8x8 board where each side promotes on the opposite back rank,
+    // also returning its halfmove clock and fullmove number, which default to 0 and 1 when left out
+    // - castling rights are given to the king and the rook they involve: K and Q designate the outermost rooks
+    //   of the back rank, and Shredder-FEN file letters a specific rook
+    // - the en passant square marks the pawn in front of it as having just dashed
+    // - pawns can dash from their initial rank only
+    pub fn from_fen_with_clocks(fen: &str) -> Result<(Self, u32, u32), &'static str> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let [placement, side, castling, en_passant, ref clocks @ ..] = fields[..] else {
+            return Err("FEN must have at least 4 space-separated fields");
+        };
+        let (halfmove_clock, fullmove_number) = match clocks {
+            [] => (0, 1),
+            [halfmove, fullmove] => (
+                halfmove.parse().map_err(|_| "Invalid halfmove clock in FEN")?,
+                fullmove.parse().map_err(|_| "Invalid fullmove number in FEN")?,
+            ),
+            _ => return Err("FEN must have 4 or 6 space-separated fields"),
+        };
+
+        let rows: Vec<&str> = placement.split('/').collect();
+        if rows.len() != FEN_SIZE {
+            return Err("FEN placement must have 8 ranks");
+        }
+
+        let mut spaces = Array2::from_shape_fn((FEN_SIZE, FEN_SIZE), |(_, y)| Square {
+            slot: None,
+            promotes: [y == FEN_SIZE - 1, y == 0],
+        });
+        for (row_index, row) in rows.iter().enumerate() {
+            let y = FEN_SIZE - 1 - row_index;
+            let mut x = 0;
+            for symbol in row.chars() {
+                if let Some(run) = symbol.to_digit(10).filter(|run| (1..=8).contains(run)) {
+                    x += run as usize;
+                    continue;
+                }
+
+                let mut piece = Piece::from_symbol(symbol).ok_or("Invalid piece symbol in FEN")?;
+                match &mut piece.model {
+                    King { can_castle } | Rook { can_castle } => *can_castle = false,
+                    Pawn { can_dash, .. } => {
+                        if y == 0 || y == FEN_SIZE - 1 {
+                            return Err("Pawn on a back rank in FEN");
+                        }
+                        *can_dash = y == pawn_start_rank(piece.side);
+                    }
+                    _ => (),
+                }
+                if x >= FEN_SIZE {
+                    return Err("FEN rank with more than 8 squares");
+                }
+                spaces[(x, y)] = Square { slot: Some(piece), promotes: [y == FEN_SIZE - 1, y == 0] };
+                x += 1;
+            }
+            if x != FEN_SIZE {
+                return Err("FEN rank without exactly 8 squares");
+            }
+        }
+
+        let side = match side {
+            "w" => White,
+            "b" => Black,
+            _ => return Err("Invalid side to move in FEN"),
+        };
+
+        if castling != "-" {
+            for right in castling.chars() {
+                let castling_side = if right.is_ascii_uppercase() { White } else { Black };
+                let y = match castling_side {
+                    White => 0,
+                    Black => FEN_SIZE - 1,
+                };
+                let pieces_x = |wanted: fn(&PieceModel) -> bool| -> Vec<usize> {
+                    (0..FEN_SIZE)
+                        .filter(|&x| {
+                            matches!(spaces[(x, y)], Square { slot: Some(piece), .. } if piece.side == castling_side && wanted(&piece.model))
                         })
-                })
-                .collect(),
-            )
-            .unwrap()
-            .reversed_axes(),
+                        .collect()
+                };
+
+                let king_x = *pieces_x(|model| matches!(model, King { .. }))
+                    .first()
+                    .ok_or("Castling right without a king on its back rank in FEN")?;
+                let rooks_x = pieces_x(|model| matches!(model, Rook { .. }));
+                let rook_x = match right.to_ascii_lowercase() {
+                    'k' => rooks_x.iter().copied().rfind(|&x| x > king_x),
+                    'q' => rooks_x.iter().copied().find(|&x| x < king_x),
+                    file @ 'a'..='h' => rooks_x.iter().copied().find(|&x| x == (file as u8 - b'a') as usize),
+                    _ => return Err("Invalid castling right in FEN"),
+                }
+                .ok_or("Castling right without a matching rook in FEN")?;
+
+                for x in [king_x, rook_x] {
+                    if let Square { slot: Some(Piece { model: King { can_castle } | Rook { can_castle }, .. }), .. } = &mut spaces[(x, y)] {
+                        *can_castle = true;
+                    }
+                }
+            }
+        }
+
+        if en_passant != "-" {
+            let [file @ b'a'..=b'h', rank] = en_passant.as_bytes()[..] else {
+                return Err("Invalid en passant square in FEN");
+            };
+            // The pawn that just dashed belongs to the side that is not to move, and stands in front of the square
+            let (expected_rank, pawn_y) = match side {
+                White => (b'6', FEN_SIZE - 4),
+                Black => (b'3', 3),
+            };
+            if rank != expected_rank {
+                return Err("En passant square on the wrong rank in FEN");
+            }
+            let Square { slot: Some(Piece { side: pawn_side, model: Pawn { just_dashed, .. } }), .. } =
+                &mut spaces[((file - b'a') as usize, pawn_y)]
+            else {
+                return Err("En passant square without a pawn in front of it in FEN");
+            };
+            if *pawn_side == side {
+                return Err("En passant square without a pawn in front of it in FEN");
+            }
+            *just_dashed = true;
+        }
+
+        let mut board = Board {
+            spaces,
+            side,
             captured: vec![],
-            side: White,
-        })
+            hash: 0,
+        };
+        board.hash = board.compute_hash();
+        Ok((board, halfmove_clock, fullmove_number))
+    }
+
+    // Exports the board as a standard FEN record, if it follows the standard rules closely enough to be expressed in one:
+    // an 8x8 board without holes, each side promoting on the opposite back rank, pawns able to dash from their initial
+    // rank only, and castling kings on their back rank
+    // Castling rights use K and Q for the outermost rooks, and Shredder-FEN file letters for the others
+    pub fn to_fen(&self, halfmove_clock: u32, fullmove_number: u32) -> Result<String, &'static str> {
+        if self.spaces.dim() != (FEN_SIZE, FEN_SIZE) {
+            return Err("FEN boards must be 8x8");
+        }
+
+        let mut ranks = vec![];
+        let mut en_passant = String::from("-");
+        for y in (0..FEN_SIZE).rev() {
+            let mut rank = String::new();
+            let mut empty_run = 0;
+            for x in 0..FEN_SIZE {
+                let Square { slot, promotes } = self.spaces[(x, y)] else {
+                    return Err("FEN boards can't have holes");
+                };
+                if promotes != [y == FEN_SIZE - 1, y == 0] {
+                    return Err("FEN boards must promote on the back ranks only");
+                }
+
+                let Some(piece) = slot else {
+                    empty_run += 1;
+                    continue;
+                };
+                if empty_run > 0 {
+                    rank.push_str(&empty_run.to_string());
+                    empty_run = 0;
+                }
+                rank.push(piece.symbol());
+
+                if let Pawn { can_dash, just_dashed } = piece.model {
+                    if y == 0 || y == FEN_SIZE - 1 || can_dash != (y == pawn_start_rank(piece.side)) {
+                        return Err("Pawn state that FEN can't express");
+                    }
+                    if just_dashed {
+                        if en_passant != "-" || piece.side == self.side {
+                            return Err("Pawn state that FEN can't express");
+                        }
+                        let target_y = if piece.side == White { y - 1 } else { y + 1 };
+                        en_passant = format!("{}{}", (b'a' + x as u8) as char, target_y + 1);
+                    }
+                }
+            }
+            if empty_run > 0 {
+                rank.push_str(&empty_run.to_string());
+            }
+            ranks.push(rank);
+        }
+
+        let mut castling = String::new();
+        for side in [White, Black] {
+            let back_rank = if side == White { 0 } else { FEN_SIZE - 1 };
+            let pieces: Vec<(usize, usize, PieceModel)> = self
+                .spaces
+                .indexed_iter()
+                .filter_map(|((x, y), space)| match space {
+                    Square { slot: Some(piece), .. } if piece.side == side => Some((x, y, piece.model)),
+                    _ => None,
+                })
+                .collect();
+
+            let castling_kings: Vec<(usize, usize)> = pieces
+                .iter()
+                .filter(|(.., model)| *model == King { can_castle: true })
+                .map(|&(x, y, _)| (x, y))
+                .collect();
+            let king_x = match castling_kings[..] {
+                [] => continue,
+                [(x, y)] if y == back_rank => x,
+                _ => return Err("Castling rights that FEN can't express"),
+            };
+
+            let rooks: Vec<(usize, bool)> = pieces
+                .iter()
+                .filter_map(|&(x, y, model)| match model {
+                    Rook { can_castle } if y == back_rank => Some((x, can_castle)),
+                    _ => None,
+                })
+                .collect();
+            let outermost_east = rooks.iter().map(|&(x, _)| x).filter(|&x| x > king_x).max();
+            let outermost_west = rooks.iter().map(|&(x, _)| x).filter(|&x| x < king_x).min();
+
+            let mut rights: Vec<char> = rooks
+                .iter()
+                .filter(|&&(_, can_castle)| can_castle)
+                .map(|&(x, _)| match Some(x) {
+                    east if east == outermost_east => 'K',
+                    west if west == outermost_west => 'Q',
+                    _ => (b'A' + x as u8) as char,
+                })
+                .collect();
+            rights.sort_by_key(|&right| match right {
+                'K' => 0,
+                'Q' => 1,
+                file => 2 + file as u32,
+            });
+
+            castling.extend(rights.into_iter().map(|right| match side {
+                White => right,
+                Black => right.to_ascii_lowercase(),
+            }));
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        Ok(format!(
+            "{} {} {} {} {} {}",
+            ranks.join("/"),
+            match self.side {
+                White => 'w',
+                Black => 'b',
+            },
+            castling,
+            en_passant,
+            halfmove_clock,
+            fullmove_number
+        ))
+    }
+}
+
+// Rank from which pawns of a side dash in standard chess
+fn pawn_start_rank(side: Side) -> usize {
+    match side {
+        White => 1,
+        Black => FEN_SIZE - 2,
+    }
+}
+
+fn piece_notation(piece: &Piece) -> String {
+    let mut notation = String::from(piece.symbol());
+    match piece.model {
+        King { can_castle: false } | Rook { can_castle: false } => notation.push('\''),
+        Pawn { can_dash, just_dashed } => {
+            if !can_dash {
+                notation.push('\'');
+            }
+            if just_dashed {
+                notation.push('!');
+            }
+        }
+        _ => (),
+    }
+    notation
+}
+
+fn parse_piece_notation(symbol: char, markers: &mut Peekable<Chars>) -> Result<Piece, &'static str> {
+    let mut piece = Piece::from_symbol(symbol).ok_or("Invalid piece symbol in position notation")?;
+
+    let mut seen_markers = vec![];
+    while let Some(&marker) = markers.peek() {
+        if seen_markers.contains(&marker) {
+            return Err("Repeated state marker in position notation");
+        }
+        seen_markers.push(marker);
+
+        match (marker, &mut piece.model) {
+            ('\'', King { can_castle } | Rook { can_castle }) => *can_castle = false,
+            ('\'', Pawn { can_dash, .. }) => *can_dash = false,
+            ('!', Pawn { just_dashed, .. }) => *just_dashed = true,
+            ('\'' | '!', _) => return Err("Invalid state marker in position notation"),
+            _ => break,
+        }
+        markers.next();
+    }
+
+    Ok(piece)
+}
+
+// Reads a run of squares, which may not be longer than max_run
+fn parse_run(first_digit: char, digits: &mut Peekable<Chars>, max_run: usize) -> Result<usize, &'static str> {
+    let mut run = String::from(first_digit);
+    while let Some(&digit) = digits.peek().filter(|c| c.is_ascii_digit()) {
+        run.push(digit);
+        digits.next();
+    }
+
+    match run.parse() {
+        Ok(0) | Err(_) => Err("Invalid run length in position notation"),
+        Ok(run) if run > max_run => Err("Run longer than the row in position notation"),
+        Ok(run) => Ok(run),
     }
 }
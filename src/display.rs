@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+
+use chess2::board::*;
+use chess2::logic::*;
+use chess2::turns::*;
+
+// Distance in squares between a finger and the piece it drags, so that the piece stays visible
+pub const TOUCH_LIFT_OFFSET: f32 = 0.8;
+
+// Square a position falls on, positions being measured in squares from the center of the bottom-left square
+pub fn square_at([x, y]: [f32; 2]) -> Coords {
+    Coords {
+        x: x.round() as isize,
+        y: y.round() as isize,
+    }
+}
+
+// Position where a dragged piece is shown and dropped: under the mouse cursor, or above a finger,
+// screen_up being the unit vector pointing to the top of the screen in board positions
+pub fn drop_position(pointer: [f32; 2], screen_up: [f32; 2], touch: bool) -> [f32; 2] {
+    if touch {
+        [pointer[0] + screen_up[0] * TOUCH_LIFT_OFFSET, pointer[1] + screen_up[1] * TOUCH_LIFT_OFFSET]
+    } else {
+        pointer
+    }
+}
+
+// Piece selected to be moved, by clicking or dragging it
+// Selections belong to the displayed turn, and are reset whenever it changes
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Selection {
+    // Square of the selected piece
+    pub piece: Option<Coords>,
+    // Whether the selected piece follows the pointer, rather than waiting for a click on its target
+    pub held: bool,
+    // Whether the selected piece was already selected before being held, so that a click on it deselects it
+    pub reselected: bool,
+}
+
+// What the display must do after the pointer was pressed or released
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SelectionUpdate {
+    // The selected piece, if any, was deselected, then the piece on a square was selected and held
+    PickedUp { coords: Coords },
+    // The held piece was put back on its square, staying selected until its target is clicked
+    PutDown,
+    // The selected piece, if any, was deselected, playing the moves to the square it was clicked or dropped on:
+    // none when it goes back to its square, several when they are the promotion choices of a single move
+    Deselected { moves: Vec<Move> },
+}
+
+impl Selection {
+    // Moves of the selected piece to a square
+    pub fn moves_to(&self, turn: &Turn, target: Coords) -> Vec<Move> {
+        self.piece
+            .and_then(|piece| turn.possible_moves.get(&piece))
+            .map(|moves| moves.iter().filter(|move_| move_.target == target).copied().collect())
+            .unwrap_or_default()
+    }
+
+    // Handles a press of the pointer on a square
+    // A press on a target of the piece waiting for a click moves it there, otherwise a piece of the side to move
+    // is picked up, unless can_move is false, e.g. when the computer plays that side
+    pub fn press(&mut self, turn: &Turn, pointer: Coords, can_move: bool) -> SelectionUpdate {
+        let clicked_moves = if self.held { vec![] } else { self.moves_to(turn, pointer) };
+        let previous_piece = self.piece;
+        *self = Selection::default();
+
+        if !clicked_moves.is_empty() {
+            return SelectionUpdate::Deselected { moves: clicked_moves };
+        }
+
+        let movable = matches!(
+            turn.board.spaces.get(pointer),
+            Some(Space::Square { slot: Some(piece), .. }) if piece.side == turn.board.side
+        );
+        if !movable || !can_move || turn.outcome != GameOutcome::Ongoing {
+            return SelectionUpdate::Deselected { moves: vec![] };
+        }
+
+        *self = Selection {
+            piece: Some(pointer),
+            held: true,
+            reselected: previous_piece == Some(pointer),
+        };
+        SelectionUpdate::PickedUp { coords: pointer }
+    }
+
+    // Handles the release of the pointer, while a piece is held
+    // Released on its own square, the piece stays selected for a click on its target,
+    // unless it was already selected, in which case the click deselects it
+    // Taps are told apart from drags by the pointer's square rather than by the square the piece is dropped on
+    pub fn release(&mut self, turn: &Turn, pointer: Coords, drop: Coords) -> Option<SelectionUpdate> {
+        let piece = self.piece.filter(|_| self.held)?;
+
+        if pointer == piece && !self.reselected {
+            self.held = false;
+            return Some(SelectionUpdate::PutDown);
+        }
+
+        let moves = self.moves_to(turn, drop);
+        *self = Selection::default();
+        Some(SelectionUpdate::Deselected { moves })
+    }
+}
+
+// Pieces to slide and to fade out when a move is displayed
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct AnimationPlan {
+    // Pieces sliding to a square, from the square they come from
+    pub slides: Vec<(Coords, Coords)>,
+    // Square the captured piece fades out on
+    pub captured: Option<Coords>,
+    // Seconds taken by the longest slide and by the fading
+    pub duration: f32,
+}
+
+// Plans the animation of a move at a positive speed, in squares per second
+// The moving piece doesn't slide when it was dropped on its target, where it already is, but a castling rook still does
+pub fn animation_plan(move_: &Move, dropped: bool, speed: f32) -> AnimationPlan {
+    let mut slides = vec![];
+    if !dropped {
+        slides.push((move_.source, move_.target));
+    }
+    slides.extend(move_.castle_rook_move());
+
+    let longest_slide = slides
+        .iter()
+        .map(|(from, to)| (((to.x - from.x).pow(2) + (to.y - from.y).pow(2)) as f32).sqrt())
+        .fold(0., f32::max);
+
+    AnimationPlan {
+        slides,
+        captured: move_.captured_coords(),
+        // Captures of a dropped piece still take a square's time to fade out
+        duration: longest_slide.max(1.) / speed,
+    }
+}
+
+// Tint of a square showing what happened on it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SquareHighlight {
+    LastMove,
+    Check,
+}
+
+// Squares the last move went from and to, those of the castling rook included,
+// and those of the kings in check of the side to move, which take precedence
+pub fn square_highlights(turn: &Turn) -> HashMap<Coords, SquareHighlight> {
+    let previous_move = turn.previous_move;
+    let mut highlights = HashMap::new();
+    if previous_move.kind != MoveKind::Skip {
+        highlights.insert(previous_move.source, SquareHighlight::LastMove);
+        highlights.insert(previous_move.target, SquareHighlight::LastMove);
+    }
+    if let Some((rook_source, rook_target)) = previous_move.castle_rook_move() {
+        highlights.insert(rook_source, SquareHighlight::LastMove);
+        highlights.insert(rook_target, SquareHighlight::LastMove);
+    }
+    for king in checked_kings(&turn.board) {
+        highlights.insert(king, SquareHighlight::Check);
+    }
+    highlights
+}
+
+// Tests of the display logic: piece selection by clicks and drags, the squares pieces are dropped on, move animations and square highlights
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn_from(squares: &str) -> Turn {
+        let board = Board::from_notation(&format!("{} WWWWWWWW/8/8/8/8/8/8/bbbbbbbb w -", squares)).unwrap();
+        Turn::new(board, Move::skip())
+    }
+
+    fn coords(x: isize, y: isize) -> Coords {
+        Coords { x, y }
+    }
+
+    fn moves(turn: &Turn, source: Coords, target: Coords) -> Vec<Move> {
+        turn.possible_moves[&source].iter().filter(|move_| move_.target == target).copied().collect()
+    }
+
+    #[test]
+    fn click_to_move() {
+        let turn = turn_from("k7/8/8/8/8/8/4P3/K7");
+        let (pawn, target) = (coords(4, 1), coords(4, 3));
+        let mut selection = Selection::default();
+
+        // Pressing then releasing a piece on its square leaves it selected
+        assert_eq!(selection.press(&turn, pawn, true), SelectionUpdate::PickedUp { coords: pawn });
+        assert_eq!(selection, Selection { piece: Some(pawn), held: true, reselected: false });
+        assert_eq!(selection.release(&turn, pawn, pawn), Some(SelectionUpdate::PutDown));
+        assert_eq!(selection, Selection { piece: Some(pawn), held: false, reselected: false });
+
+        // Clicking one of its targets plays the move
+        assert_eq!(selection.press(&turn, target, true), SelectionUpdate::Deselected { moves: moves(&turn, pawn, target) });
+        assert_eq!(selection, Selection::default());
+        // The release ending that click does nothing
+        assert_eq!(selection.release(&turn, target, target), None);
+    }
+
+    #[test]
+    fn click_to_deselect() {
+        let turn = turn_from("k7/8/8/8/8/8/4P3/K7");
+        let pawn = coords(4, 1);
+        let mut selection = Selection::default();
+        selection.press(&turn, pawn, true);
+        selection.release(&turn, pawn, pawn);
+
+        // A second click on the selected piece deselects it
+        assert_eq!(selection.press(&turn, pawn, true), SelectionUpdate::PickedUp { coords: pawn });
+        assert!(selection.reselected);
+        assert_eq!(selection.release(&turn, pawn, pawn), Some(SelectionUpdate::Deselected { moves: vec![] }));
+        assert_eq!(selection, Selection::default());
+
+        // So does a click on a square that isn't a target, while a click on another piece selects it instead
+        selection.press(&turn, pawn, true);
+        selection.release(&turn, pawn, pawn);
+        assert_eq!(selection.press(&turn, coords(4, 4), true), SelectionUpdate::Deselected { moves: vec![] });
+        assert_eq!(selection, Selection::default());
+
+        selection.press(&turn, pawn, true);
+        selection.release(&turn, pawn, pawn);
+        assert_eq!(selection.press(&turn, coords(0, 0), true), SelectionUpdate::PickedUp { coords: coords(0, 0) });
+        assert_eq!(selection, Selection { piece: Some(coords(0, 0)), held: true, reselected: false });
+    }
+
+    #[test]
+    fn drag_to_move() {
+        let turn = turn_from("k7/8/8/8/8/8/4P3/K7");
+        let (pawn, target) = (coords(4, 1), coords(4, 2));
+        let mut selection = Selection::default();
+
+        selection.press(&turn, pawn, true);
+        assert_eq!(selection.release(&turn, target, target), Some(SelectionUpdate::Deselected { moves: moves(&turn, pawn, target) }));
+        assert_eq!(selection, Selection::default());
+
+        // Dropped on a square it can't go to, the piece goes back without staying selected
+        selection.press(&turn, pawn, true);
+        assert_eq!(selection.release(&turn, coords(5, 2), coords(5, 2)), Some(SelectionUpdate::Deselected { moves: vec![] }));
+        assert_eq!(selection, Selection::default());
+
+        // Promotions give every choice of piece
+        let turn = turn_from("k7/1P6/8/8/8/8/8/K7");
+        let (pawn, target) = (coords(1, 6), coords(1, 7));
+        selection.press(&turn, pawn, true);
+        let Some(SelectionUpdate::Deselected { moves }) = selection.release(&turn, target, target) else { panic!() };
+        assert_eq!(moves.len(), 4);
+        assert!(moves.iter().all(|move_| move_.promotion.is_some()));
+    }
+
+    #[test]
+    fn unmovable_pieces() {
+        let turn = turn_from("k7/8/8/8/8/8/4P3/K7");
+        let mut selection = Selection::default();
+
+        // Pieces of the side not to move, empty squares, and pieces of a side the player doesn't control
+        assert_eq!(selection.press(&turn, coords(0, 7), true), SelectionUpdate::Deselected { moves: vec![] });
+        assert_eq!(selection.press(&turn, coords(3, 3), true), SelectionUpdate::Deselected { moves: vec![] });
+        assert_eq!(selection.press(&turn, coords(4, 1), false), SelectionUpdate::Deselected { moves: vec![] });
+        assert_eq!(selection, Selection::default());
+
+        // Nothing can be picked up once the game is over
+        let mated = Turn::new(
+            Board::from_notation("k7/1Q6/1K6/8/8/8/8/8 WWWWWWWW/8/8/8/8/8/8/bbbbbbbb b -").unwrap(),
+            Move::skip(),
+        );
+        assert_eq!(mated.outcome, GameOutcome::Checkmate { winner: Side::White });
+        assert_eq!(selection.press(&mated, coords(0, 7), true), SelectionUpdate::Deselected { moves: vec![] });
+    }
+
+    #[test]
+    fn drop_squares() {
+        // Positions are rounded to the nearest square center
+        assert_eq!(square_at([0.49, -0.49]), coords(0, 0));
+        assert_eq!(square_at([2.51, 6.6]), coords(3, 7));
+        assert_eq!(square_at([-0.6, 8.2]), coords(-1, 8));
+
+        // The mouse drops pieces where it points, fingers below the piece they lift towards the top of the screen
+        assert_eq!(drop_position([3., 2.5], [0., 1.], false), [3., 2.5]);
+        assert_eq!(square_at(drop_position([3., 2.5], [0., 1.], true)), coords(3, 3));
+        assert_eq!(square_at(drop_position([3., 2.5], [0., -1.], true)), coords(3, 2));
+        assert_eq!(square_at(drop_position([3., 2.], [0., 1.], true)), square_at([3., 2. + TOUCH_LIFT_OFFSET]));
+    }
+
+    #[test]
+    fn touch_release_targets() {
+        let turn = turn_from("k7/8/8/8/8/8/4P3/K7");
+        let pawn = coords(4, 1);
+        let mut selection = Selection::default();
+
+        // A tap lifts the piece above the finger, but leaves it selected since the finger stayed on its square
+        selection.press(&turn, pawn, true);
+        let lifted = square_at(drop_position([4., 1.], [0., 1.], true));
+        assert_eq!(lifted, coords(4, 2));
+        assert_eq!(selection.release(&turn, pawn, lifted), Some(SelectionUpdate::PutDown));
+
+        // Once dragged, the move goes to the square under the lifted piece rather than under the finger
+        selection.press(&turn, pawn, true);
+        let finger = [4., 2.3];
+        let dropped = square_at(drop_position(finger, [0., 1.], true));
+        assert_eq!(dropped, coords(4, 3));
+        assert_eq!(
+            selection.release(&turn, square_at(finger), dropped),
+            Some(SelectionUpdate::Deselected { moves: moves(&turn, pawn, coords(4, 3)) })
+        );
+    }
+
+    fn move_of(turn: &Turn, [sx, sy, tx, ty]: [isize; 4]) -> Move {
+        moves(turn, coords(sx, sy), coords(tx, ty))[0]
+    }
+
+    #[test]
+    fn animation_plans() {
+        // A knight slides straight to its square, in the time of its longest slide
+        let turn = turn_from("k7/8/8/8/8/8/8/KN6");
+        let plan = animation_plan(&move_of(&turn, [1, 0, 2, 2]), false, 4.);
+        assert_eq!(plan.slides, [(coords(1, 0), coords(2, 2))]);
+        assert_eq!(plan.captured, None);
+        assert!((plan.duration - 5f32.sqrt() / 4.).abs() < 1e-6);
+
+        // Castling slides the rook next to the king, on the side it came from, even when the king was dropped
+        let turn = turn_from("k7/8/8/8/8/8/8/R3K2R");
+        let queenside = move_of(&turn, [4, 0, 2, 0]);
+        assert!(matches!(queenside.kind, MoveKind::Castle { .. }));
+        let plan = animation_plan(&queenside, false, 8.);
+        assert_eq!(plan.slides, [(coords(4, 0), coords(2, 0)), (coords(0, 0), coords(3, 0))]);
+        assert_eq!(plan.duration, 3. / 8.);
+        let plan = animation_plan(&move_of(&turn, [4, 0, 6, 0]), true, 8.);
+        assert_eq!(plan.slides, [(coords(7, 0), coords(5, 0))]);
+
+        // En passant fades out the pawn beside the target square, not on it
+        let board = Board::from_notation("k7/8/8/3p!P3/8/8/8/K7 WWWWWWWW/8/8/8/8/8/8/bbbbbbbb w -").unwrap();
+        let turn = Turn::new(board, Move::skip());
+        let en_passant = move_of(&turn, [4, 4, 3, 5]);
+        assert_eq!(en_passant.kind, MoveKind::EnPassant);
+        let plan = animation_plan(&en_passant, false, 8.);
+        assert_eq!(plan.slides, [(coords(4, 4), coords(3, 5))]);
+        assert_eq!(plan.captured, Some(coords(3, 4)));
+
+        // A dropped piece doesn't slide, but what it captured still fades out for a square's time
+        let turn = turn_from("k7/8/8/8/8/8/1r6/KR6");
+        let plan = animation_plan(&move_of(&turn, [1, 0, 1, 1]), true, 8.);
+        assert_eq!(plan.slides, []);
+        assert_eq!(plan.captured, Some(coords(1, 1)));
+        assert_eq!(plan.duration, 1. / 8.);
+    }
+
+    fn play(turn: &Turn, move_: [isize; 4]) -> Turn {
+        let move_ = move_of(turn, move_);
+        Turn::new(get_next_board(&turn.board, &move_), move_)
+    }
+
+    #[test]
+    fn highlights() {
+        use SquareHighlight::*;
+
+        // Nothing before the first move
+        let turn = turn_from("1k6/8/8/8/8/8/8/R3K2R");
+        assert!(square_highlights(&turn).is_empty());
+
+        // The squares the moving piece left and reached
+        let after_rook_move = play(&turn, [7, 0, 7, 5]);
+        assert_eq!(square_highlights(&after_rook_move), [(coords(7, 0), LastMove), (coords(7, 5), LastMove)].into());
+
+        // Castling shows the rook's squares too
+        let after_castling = play(&turn, [4, 0, 2, 0]);
+        assert_eq!(
+            square_highlights(&after_castling),
+            [(coords(4, 0), LastMove), (coords(2, 0), LastMove), (coords(0, 0), LastMove), (coords(3, 0), LastMove)].into()
+        );
+
+        // Kings in check of the side to move, all of them when there are several
+        let after_check = play(&turn, [0, 0, 1, 0]);
+        assert_eq!(
+            square_highlights(&after_check),
+            [(coords(0, 0), LastMove), (coords(1, 0), LastMove), (coords(1, 7), Check)].into()
+        );
+        let two_kings = turn_from("k6k/8/8/8/8/8/8/K5R1");
+        let after_double_check = play(&two_kings, [6, 0, 6, 7]);
+        assert_eq!(
+            square_highlights(&after_double_check),
+            [(coords(6, 0), LastMove), (coords(6, 7), LastMove), (coords(0, 7), Check), (coords(7, 7), Check)].into()
+        );
+    }
+}
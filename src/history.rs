@@ -0,0 +1,126 @@
+use bevy::prelude::*;
+use chess2::turns::*;
+
+use crate::ai_player::AIPlayers;
+
+#[cfg(target_family = "wasm")]
+use crate::io_wasm::*;
+#[cfg(not(target_family = "wasm"))]
+use crate::io_standard::*;
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (navigate_history, report_history_position).chain());
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum HistoryCommand {
+    Back,
+    Forward,
+    Start,
+    End,
+    Undo,
+    Redo,
+}
+
+impl HistoryCommand {
+    // Commands queued by the page, named after their buttons
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "back" => Some(HistoryCommand::Back),
+            "forward" => Some(HistoryCommand::Forward),
+            "start" => Some(HistoryCommand::Start),
+            "end" => Some(HistoryCommand::End),
+            "undo" => Some(HistoryCommand::Undo),
+            "redo" => Some(HistoryCommand::Redo),
+            _ => None,
+        }
+    }
+
+    fn from_keys(keys: &ButtonInput<KeyCode>) -> Option<Self> {
+        let control = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight, KeyCode::SuperLeft, KeyCode::SuperRight]);
+        let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        if control {
+            return if keys.just_pressed(KeyCode::KeyZ) {
+                Some(if shift { HistoryCommand::Redo } else { HistoryCommand::Undo })
+            } else if keys.just_pressed(KeyCode::KeyY) {
+                Some(HistoryCommand::Redo)
+            } else {
+                None
+            };
+        }
+
+        [
+            (KeyCode::ArrowLeft, HistoryCommand::Back),
+            (KeyCode::ArrowRight, HistoryCommand::Forward),
+            (KeyCode::Home, HistoryCommand::Start),
+            (KeyCode::End, HistoryCommand::End),
+        ]
+        .into_iter()
+        .find(|(key, _)| keys.just_pressed(*key))
+        .map(|(_, command)| command)
+    }
+
+    // Turn reached from the current one, moving forward along the first continuation of each turn
+    fn target(self, turns: &Turns) -> Option<TurnId> {
+        let current = turns.current_id();
+        match self {
+            HistoryCommand::Back => turns.parent(current),
+            HistoryCommand::Forward => turns.children(current).first().copied(),
+            HistoryCommand::Start => turns.line(current).first().copied(),
+            HistoryCommand::End => Some(turns.line_end(current)),
+            HistoryCommand::Undo | HistoryCommand::Redo => None,
+        }
+    }
+}
+
+// Whether the side to play in the current turn is the computer while the other one isn't,
+// in which case undo and redo skip over its turn so that it doesn't immediately replay
+fn computer_to_play_against_human(turns: &Turns, ai_players: &AIPlayers) -> bool {
+    turns.current().is_some_and(|turn| {
+        ai_players.sides[turn.board.side as usize] && !ai_players.sides[turn.board.side.other() as usize]
+    })
+}
+
+fn navigate_history(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut turns: ResMut<Turns>,
+    ai_players: Res<AIPlayers>
+) {
+    let polled_command = HistoryCommand::from_name(&poll_history_command());
+    let Some(command) = polled_command.or_else(|| HistoryCommand::from_keys(&keys)) else { return };
+
+    match command {
+        HistoryCommand::Undo => {
+            if turns.undo().is_some() && computer_to_play_against_human(&turns, &ai_players) {
+                turns.undo();
+            }
+        }
+        HistoryCommand::Redo => {
+            if turns.redo().is_some() && computer_to_play_against_human(&turns, &ai_players) && turns.can_redo() {
+                turns.redo();
+            }
+        }
+        _ => {
+            if let Some(target) = command.target(&turns).filter(|&target| target != turns.current_id()) {
+                turns.go_to(target);
+            }
+        }
+    }
+}
+
+// Tells the page which move of its line the displayed turn is, and whether moves follow it
+fn report_history_position(
+    turns: Res<Turns>
+) {
+    if !turns.is_changed() { return; }
+
+    let current = turns.current_id();
+    let move_number = turns.line(current).len().saturating_sub(1);
+    let line_length = turns.line(turns.line_end(current)).len().saturating_sub(1);
+
+    set_history_position(move_number as u32, line_length as u32);
+}
@@ -16,6 +16,22 @@ pub fn poll_restart() -> bool {
     SHOULD_RESTART.swap(false, Ordering::Relaxed)
 }
 
+pub fn poll_export() -> bool {
+    false
+}
+
+pub fn poll_history_command() -> String {
+    String::new()
+}
+
+pub fn poll_game_export() -> bool {
+    false
+}
+
+pub fn poll_game_import() -> bool {
+    false
+}
+
 pub fn get_pieces_string() -> String {
     String::from("\
 rnbqkbnr
@@ -40,6 +56,56 @@ ________
 bbbbbbbb")
 }
 
+pub fn get_strict_layout() -> bool {
+    true
+}
+
 pub fn get_bottom_side() -> String {
     String::from("white")
-}
\ No newline at end of file
+}
+
+pub fn get_animation_speed() -> String {
+    String::from("8")
+}
+
+pub fn get_white_player() -> String {
+    String::from("human")
+}
+
+pub fn get_black_player() -> String {
+    String::from("human")
+}
+
+pub fn set_position_notation(notation: &str) {
+    println!("Position: {}", notation);
+}
+
+pub fn set_position_fen(fen: &str) {
+    if !fen.is_empty() {
+        println!("FEN: {}", fen);
+    }
+}
+
+pub fn set_layout_warnings(warnings: &str) {
+    if !warnings.is_empty() {
+        println!("{}", warnings);
+    }
+}
+
+pub fn set_layout_strings(pieces_string: &str, promotions_string: &str) {
+    println!("Pieces:\n{}\nPromotions:\n{}", pieces_string, promotions_string);
+}
+
+pub fn set_history_position(move_number: u32, line_length: u32) {
+    if move_number < line_length {
+        println!("Viewing move {} of {}", move_number, line_length);
+    }
+}
+
+pub fn get_game_record() -> String {
+    String::new()
+}
+
+pub fn set_game_record(record: &str) {
+    println!("{}", record);
+}
@@ -10,7 +10,22 @@ extern "C" {
     pub fn log(s: &str);
 
     pub fn poll_restart() -> bool;
+    pub fn poll_export() -> bool;
+    pub fn poll_history_command() -> String;
+    pub fn poll_game_export() -> bool;
+    pub fn poll_game_import() -> bool;
     pub fn get_pieces_string() -> String;
     pub fn get_promotions_string() -> String;
+    pub fn get_strict_layout() -> bool;
     pub fn get_bottom_side() -> String;
+    pub fn get_animation_speed() -> String;
+    pub fn get_white_player() -> String;
+    pub fn get_black_player() -> String;
+    pub fn set_position_notation(notation: &str);
+    pub fn set_position_fen(fen: &str);
+    pub fn set_layout_warnings(warnings: &str);
+    pub fn set_layout_strings(pieces_string: &str, promotions_string: &str);
+    pub fn set_history_position(move_number: u32, line_length: u32);
+    pub fn get_game_record() -> String;
+    pub fn set_game_record(record: &str);
 }
@@ -0,0 +1,8 @@
+// Rules engine of chess2, usable without Bevy by disabling the default "bevy" feature
+pub mod ai;
+pub mod board;
+pub mod logic;
+pub mod notation;
+pub mod pgn;
+pub mod turns;
+pub mod validation;
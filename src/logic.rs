@@ -1,8 +1,10 @@
+#[cfg(feature = "bevy")]
 use bevy::prelude::*;
 use core::panic;
 use std::collections::HashMap;
+use std::fmt;
+use std::mem::discriminant;
 use std::iter::from_fn;
-use std::ops::Not;
 
 use crate::board::PieceModel::*;
 use crate::board::Side::*;
@@ -11,8 +13,10 @@ use crate::board::*;
 
 const CAN_CASTLE_WITH_PROMOTED_ROOK: bool = true;
 
+#[cfg(feature = "bevy")]
 pub struct LogicPlugin;
 
+#[cfg(feature = "bevy")]
 impl Plugin for LogicPlugin {
     fn build(&self, _app: &mut App) {}
 }
@@ -38,6 +42,22 @@ pub struct Move {
     pub promotion: Option<PieceModel>,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum GameOutcome {
+    #[default]
+    Ongoing,
+    Checkmate { winner: Side },
+    Stalemate,
+    Draw { reason: DrawReason },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DrawReason {
+    FiftyMoveRule,
+    ThreefoldRepetition,
+    InsufficientMaterial,
+}
+
 impl Move {
     pub const fn skip() -> Self {
         Move {
@@ -47,186 +67,531 @@ impl Move {
             promotion: None,
         }
     }
+
+    // Square of the piece taken by a capture, which is beside the target for en passant
+    pub fn captured_coords(&self) -> Option<Coords> {
+        match self.kind {
+            MoveKind::Capture => Some(self.target),
+            MoveKind::EnPassant => Some(Coords {
+                x: self.target.x,
+                y: self.source.y,
+            }),
+            _ => None,
+        }
+    }
+
+    // Source and target squares of the rook of a castle, which lands next to the king on the side it came from
+    pub fn castle_rook_move(&self) -> Option<(Coords, Coords)> {
+        let MoveKind::Castle { rook_coords } = self.kind else { return None };
+        let rook_target = Coords {
+            x: self.target.x + (self.source.x - rook_coords.x).signum(),
+            y: rook_coords.y,
+        };
+        Some((rook_coords, rook_target))
+    }
 }
 
-// Returns a new board with a move applied
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MoveError {
+    OutOfBounds { coords: Coords },
+    NoSquare { coords: Coords },
+    NoPiece { coords: Coords },
+    WrongSide { coords: Coords },
+    Illegal,
+    MissingPromotion,
+    InvalidPromotion,
+    UnexpectedPromotion,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::OutOfBounds { coords } => write!(f, "({}, {}) is out of the board", coords.x, coords.y),
+            MoveError::NoSquare { coords } => write!(f, "({}, {}) is a hole", coords.x, coords.y),
+            MoveError::NoPiece { coords } => write!(f, "No piece at ({}, {})", coords.x, coords.y),
+            MoveError::WrongSide { coords } => write!(f, "The piece at ({}, {}) can't move this turn", coords.x, coords.y),
+            MoveError::Illegal => write!(f, "Illegal move"),
+            MoveError::MissingPromotion => write!(f, "Missing promotion"),
+            MoveError::InvalidPromotion => write!(f, "Invalid promotion"),
+            MoveError::UnexpectedPromotion => write!(f, "Promotion on a move that doesn't promote"),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+// Finds the legal move of the side to play matching a source, a target and a promotion choice
+// Only the kind of the promotion piece matters, its state (e.g. castling rights of a rook) is set by the rules
+pub fn resolve_move(
+    board: &Board,
+    source: Coords,
+    target: Coords,
+    promotion: Option<PieceModel>,
+) -> Result<Move, MoveError> {
+    for coords in [source, target] {
+        match board.spaces.get(coords) {
+            None => return Err(MoveError::OutOfBounds { coords }),
+            Some(Hole) => return Err(MoveError::NoSquare { coords }),
+            _ => (),
+        }
+    }
+    match board.spaces.get(source) {
+        Some(Square { slot: Some(piece), .. }) if piece.side != board.side => {
+            return Err(MoveError::WrongSide { coords: source });
+        }
+        Some(Square { slot: None, .. }) => return Err(MoveError::NoPiece { coords: source }),
+        _ => (),
+    }
+
+    let possible_moves = compute_possible_moves(board, true);
+    let candidates: Vec<&Move> = possible_moves
+        .get(&source)
+        .into_iter()
+        .flatten()
+        .filter(|move_| move_.target == target)
+        .collect();
+
+    if candidates.is_empty() {
+        return Err(MoveError::Illegal);
+    }
+
+    match promotion {
+        None if candidates.iter().any(|move_| move_.promotion.is_some()) => Err(MoveError::MissingPromotion),
+        None => Ok(*candidates[0]),
+        Some(model) => candidates
+            .iter()
+            .find(|move_| {
+                move_
+                    .promotion
+                    .is_some_and(|legal_model| discriminant(&legal_model) == discriminant(&model))
+            })
+            .map(|move_| **move_)
+            .ok_or(if candidates.iter().all(|move_| move_.promotion.is_none()) {
+                MoveError::UnexpectedPromotion
+            } else {
+                MoveError::InvalidPromotion
+            }),
+    }
+}
+
+// Returns a new board with a move applied, after checking that it is legal
+// The legal move matching its source, target and promotion kind is played, whatever kind and promotion state it was given
+pub fn try_apply_move(board: &Board, move_: &Move) -> Result<Board, MoveError> {
+    let resolved_move = resolve_move(board, move_.source, move_.target, move_.promotion)?;
+
+    Ok(get_next_board(board, &resolved_move))
+}
+
+// Board state overwritten by make_move, restored by unmake_move
+#[derive(Clone, Debug)]
+pub struct UndoInfo {
+    side: Side,
+    hash: u64,
+    captured_count: usize,
+    // Previous contents of the squares changed by the move, in order of change
+    slots: Vec<(Coords, Option<Piece>)>,
+}
+
+// Replaces the piece on a square, remembering the previous one for unmake_move
+fn replace_slot(board: &mut Board, undo: &mut UndoInfo, coords: Coords, piece: Option<Piece>, error: &str) -> Option<Piece> {
+    let Some(Square { slot, .. }) = board.spaces.get_mut(coords) else {
+        panic!("{}", error);
+    };
+
+    undo.slots.push((coords, *slot));
+    board.hash ^= slot_key(coords, *slot) ^ slot_key(coords, piece);
+    std::mem::replace(slot, piece)
+}
+
+// Applies a move in place, returning what is needed to take it back with unmake_move
 // Panics on impossible moves or out of bounds coords
-pub fn get_next_board(board: &Board, move_: &Move) -> Board {
-    let mut next_board = board.clone();
+pub fn make_move(board: &mut Board, move_: &Move) -> UndoInfo {
+    let mover = board.side;
+    let mut undo = UndoInfo {
+        side: mover,
+        hash: board.hash,
+        captured_count: board.captured.len(),
+        slots: vec![],
+    };
 
-    next_board.side = board.side.other();
+    board.side = mover.other();
+    board.hash ^= BLACK_TO_MOVE_KEY;
 
     // Set just_dashed to false to remove en-passant eligibility of pawns that dashed more than a turn ago
-    for space in &mut next_board.spaces {
-        if let Space::Square {
-            slot: Some(Piece {
-                model: PieceModel::Pawn { 
-                    just_dashed,
-                    ..
-                },
-                side
-            }),
-            ..
-        } = space {
-            if *side == board.side.other() {
-                *just_dashed = false;
+    for ((x, y), space) in board.spaces.indexed_iter_mut() {
+        if let Square { slot: Some(piece @ Piece { model: Pawn { just_dashed: true, .. }, .. }), .. } = space {
+            if piece.side != mover {
+                let coords = Coords { x: x as isize, y: y as isize };
+                undo.slots.push((coords, Some(*piece)));
+                board.hash ^= slot_key(coords, Some(*piece));
+                if let Pawn { just_dashed, .. } = &mut piece.model {
+                    *just_dashed = false;
+                }
+                board.hash ^= slot_key(coords, Some(*piece));
             }
         }
     }
 
     if move_.kind == MoveKind::Skip {
-        return next_board;
+        return undo;
     }
 
-    let Square { slot: ref mut source_slot, .. } = next_board.spaces[move_.source] else {
-        panic!("Invalid move, no source square");
-    };
-    let Some(mut source_piece) = *source_slot else {
+    let Some(mut source_piece) = replace_slot(board, &mut undo, move_.source, None, "Invalid move, no source square") else {
         panic!("Invalid move, no source piece");
     };
 
-    *source_slot = None;
-
-    // Any pawn move results in it not being able to dash again
-    if let Piece {
-        model: Pawn {
-            ref mut can_dash,
-            ..
-        },
-        ..
-    } = source_piece
-    {
-        *can_dash = false;
+    // Any pawn move results in it not being able to dash again, and any king or rook move in it not being able to castle
+    match &mut source_piece.model {
+        Pawn { can_dash, .. } => *can_dash = false,
+        King { can_castle } | Rook { can_castle } => *can_castle = false,
+        _ => (),
     }
 
     match move_.kind {
         MoveKind::Capture => {
-            let Square { slot: Some(captured_piece), .. } = next_board.spaces[move_.target] else {
+            let Some(&Square { slot: Some(captured_piece), .. }) = board.spaces.get(move_.target) else {
                 panic!("Invalid capture, no target piece");
             };
 
-            next_board.captured.push(captured_piece);
+            board.captured.push(captured_piece);
         }
         MoveKind::Dash => {
-            let Piece { model: Pawn { ref mut just_dashed, .. }, .. } = source_piece else {
+            let Pawn { just_dashed, .. } = &mut source_piece.model else {
                 panic!("Invalid dash, no source pawn");
             };
 
             *just_dashed = true;
         }
         MoveKind::EnPassant => {
-            let captured_coords = Coords {
-                x: move_.target.x,
-                y: move_.source.y,
-            };
-
-            let Square { slot: ref mut captured_slot, .. } = next_board.spaces[captured_coords] else {
-                panic!("Invalid en passant, no captured square - google it!");
-            };
-            let Some(captured_piece) = *captured_slot else {
+            let Some(captured_coords) = move_.captured_coords() else { unreachable!() };
+
+            let Some(captured_piece) = replace_slot(
+                board,
+                &mut undo,
+                captured_coords,
+                None,
+                "Invalid en passant, no captured square - google it!",
+            ) else {
                 panic!("Invalid en passant, no captured piece");
             };
 
-            next_board.captured.push(captured_piece);
-            *captured_slot = None;
+            board.captured.push(captured_piece);
         }
-        MoveKind::Castle { rook_coords } => {
-            let Square { slot: ref mut rook_source_slot, .. } = next_board.spaces[rook_coords] else {
-                panic!("Invalid castle, no rook source square");
-            };
-            let Some(rook) = *rook_source_slot else {
+        MoveKind::Castle { .. } => {
+            let Some((rook_coords, rook_target)) = move_.castle_rook_move() else { unreachable!() };
+            let Some(rook) = replace_slot(board, &mut undo, rook_coords, None, "Invalid castle, no rook source square") else {
                 panic!("Invalid castle, no rook");
             };
 
-            *rook_source_slot = None;
-
-            let Square { slot: ref mut rook_target_slot, .. } = next_board.spaces[Coords {
-                x: move_.target.x + (move_.source.x - rook_coords.x).signum(),
-                y: rook_coords.y,
-            }] else {
-                panic!("Invalid castle, no rook target square");
-            };
-
-            *rook_target_slot = Some(Piece {
-                model: Rook { can_castle: false },
-                ..rook
-            });
+            replace_slot(
+                board,
+                &mut undo,
+                rook_target,
+                Some(Piece {
+                    model: Rook { can_castle: false },
+                    ..rook
+                }),
+                "Invalid castle, no rook target square",
+            );
 
-            let Piece { model: King { ref mut can_castle }, .. } = source_piece else {
+            let King { .. } = source_piece.model else {
                 panic!("Invalid castle, no source king");
             };
-
-            *can_castle = false;
         }
         _ => (),
     }
 
-    let Square { slot: ref mut target_slot, .. } = next_board.spaces[move_.target] else {
-        panic!("Invalid move, no target square");
-    };
-
-    if let Some(model) = move_.promotion {
-        *target_slot = Some(Piece {
+    let target_piece = match move_.promotion {
+        Some(model) => Piece {
             model,
             ..source_piece
-        });
-    } else {
-        *target_slot = Some(source_piece);
+        },
+        None => source_piece,
+    };
+    replace_slot(board, &mut undo, move_.target, Some(target_piece), "Invalid move, no target square");
+
+    undo
+}
+
+// Takes back the move that produced an UndoInfo, which must be the last one made on this board
+pub fn unmake_move(board: &mut Board, undo: UndoInfo) {
+    for (coords, piece) in undo.slots.into_iter().rev() {
+        if let Some(Square { slot, .. }) = board.spaces.get_mut(coords) {
+            *slot = piece;
+        }
     }
 
+    board.captured.truncate(undo.captured_count);
+    board.side = undo.side;
+    board.hash = undo.hash;
+}
+
+// Returns a new board with a move applied
+// Panics on impossible moves or out of bounds coords, see try_apply_move for untrusted moves
+pub fn get_next_board(board: &Board, move_: &Move) -> Board {
+    let mut next_board = board.clone();
+    make_move(&mut next_board, move_);
     next_board
 }
 
-pub fn is_in_check_after_move(board: &Board, move_: &Move) -> bool {
-    let next_board = get_next_board(board, move_);
+// Whether a square is attacked by any piece of a side, looking outwards from that square
+// Pieces and holes block sliding attacks
+pub fn is_square_attacked(board: &Board, coords: Coords, attacker: Side) -> bool {
+    let Coords { x, y } = coords;
+    let attacker_at = |target: Coords| match board.spaces.get(target) {
+        Some(Square { slot: Some(piece), .. }) if piece.side == attacker => Some(piece.model),
+        _ => None,
+    };
 
-    let king_coords: Vec<_> = next_board
+    let knight_attack = [[1, 2], [2, 1], [2, -1], [1, -2], [-1, -2], [-2, -1], [-2, 1], [-1, 2isize]]
+        .into_iter()
+        .any(|[dx, dy]| matches!(attacker_at(Coords { x: x + dx, y: y + dy }), Some(Knight)));
+
+    let king_attack = [[-1, 1], [0, 1], [1, 1], [-1, 0], [1, 0], [-1, -1], [0, -1], [1, -1isize]]
+        .into_iter()
+        .any(|[dx, dy]| matches!(attacker_at(Coords { x: x + dx, y: y + dy }), Some(King { .. })));
+
+    // Pawns capture diagonally forward, so they attack from diagonally behind the square
+    let pawn_y = if attacker == White { y - 1 } else { y + 1 };
+    let pawn_attack = [x - 1, x + 1]
+        .into_iter()
+        .any(|pawn_x| matches!(attacker_at(Coords { x: pawn_x, y: pawn_y }), Some(Pawn { .. })));
+
+    let sliding_attack = |directions: [[isize; 2]; 4], is_slider: fn(PieceModel) -> bool| {
+        directions.into_iter().any(|[dx, dy]| {
+            let mut target = coords;
+            loop {
+                target = Coords { x: target.x + dx, y: target.y + dy };
+                match board.spaces.get(target) {
+                    Some(Square { slot: None, .. }) => continue,
+                    Some(Square { slot: Some(piece), .. }) => return piece.side == attacker && is_slider(piece.model),
+                    _ => return false,
+                }
+            }
+        })
+    };
+
+    knight_attack
+        || king_attack
+        || pawn_attack
+        || sliding_attack([[-1, 0], [0, 1], [0, -1], [1, 0]], |model| matches!(model, Rook { .. } | Queen))
+        || sliding_attack([[-1, 1], [1, 1], [-1, -1], [1, -1]], |model| matches!(model, Bishop | Queen))
+}
+
+fn king_coords(board: &Board, side: Side) -> Vec<Coords> {
+    board
         .spaces
         .indexed_iter()
-        .filter_map(|((x, y), space)| {
-            if let Space::Square {
-                slot:
-                    Some(Piece {
-                        side: piece_side,
-                        model: King { .. },
-                    }),
-                ..
-            } = space
-            {
-                if *piece_side == board.side {
-                    Some(Coords {
-                        x: x as isize,
-                        y: y as isize,
-                    })
-                } else {
-                    None
-                }
-            } else {
-                None
+        .filter_map(|((x, y), space)| match space {
+            Square { slot: Some(Piece { model: King { .. }, side: king_side }), .. } if *king_side == side => {
+                Some(Coords { x: x as isize, y: y as isize })
             }
+            _ => None,
         })
-        .collect();
+        .collect()
+}
 
-    compute_possible_moves(&next_board, false)
-        .iter()
-        .all(|(_, enemy_moves)| {
-            enemy_moves.iter().all(|enemy_move| {
-                if let Move {
-                    kind: MoveKind::Capture | MoveKind::EnPassant,
-                    target,
-                    ..
-                } = enemy_move
-                {
-                    king_coords.iter().all(|coords| *target != *coords)
-                } else {
-                    true
-                }
+pub fn is_in_check_after_move(board: &Board, move_: &Move) -> bool {
+    let next_board = get_next_board(board, move_);
+
+    king_coords(&next_board, board.side)
+        .into_iter()
+        .any(|coords| is_square_attacked(&next_board, coords, board.side.other()))
+}
+
+// Whether any king of the side to move is currently attacked
+pub fn is_in_check(board: &Board) -> bool {
+    !checked_kings(board).is_empty()
+}
+
+// Squares of the kings of the side to move that are attacked, of which there may be several on multi-king layouts
+pub fn checked_kings(board: &Board) -> Vec<Coords> {
+    king_coords(board, board.side)
+        .into_iter()
+        .filter(|&coords| is_square_attacked(board, coords, board.side.other()))
+        .collect()
+}
+
+// Outcome of a position given its legal moves, as returned by compute_possible_moves with check filtering
+// A side without any legal move is checkmated if one of its kings is attacked, stalemated otherwise
+// Draws that depend on the game history (fifty-move rule, repetitions) are handled by Turns
+pub fn compute_outcome(board: &Board, possible_moves: &HashMap<Coords, Vec<Move>>) -> GameOutcome {
+    if possible_moves.values().any(|moves| !moves.is_empty()) {
+        if is_insufficient_material(board) {
+            GameOutcome::Draw { reason: DrawReason::InsufficientMaterial }
+        } else {
+            GameOutcome::Ongoing
+        }
+    } else if is_in_check(board) {
+        GameOutcome::Checkmate { winner: board.side.other() }
+    } else {
+        GameOutcome::Stalemate
+    }
+}
+
+// Whether neither side can ever checkmate the other, whatever moves are played
+// Kings can never give check by themselves. Lone minor pieces are only considered harmless against a lone king
+// if no mating pattern fits anywhere on this board: holes and odd edges can trap a king where an 8x8 board wouldn't
+pub fn is_insufficient_material(board: &Board) -> bool {
+    let mut kings = [0usize; 2];
+    let mut others: [Vec<PieceModel>; 2] = [vec![], vec![]];
+
+    for space in &board.spaces {
+        if let Square { slot: Some(piece), .. } = space {
+            match piece.model {
+                King { .. } => kings[piece.side as usize] += 1,
+                model => others[piece.side as usize].push(model),
+            }
+        }
+    }
+
+    [White, Black].into_iter().all(|side| {
+        let (own, enemy) = (side as usize, side.other() as usize);
+        match others[own][..] {
+            [] => true,
+            [model @ (Knight | Bishop)] if kings[own] == 1 && kings[enemy] == 1 && others[enemy].is_empty() => {
+                !mating_pattern_exists(board, model)
+            }
+            _ => false,
+        }
+    })
+}
+
+fn chebyshev_distance(a: Coords, b: Coords) -> isize {
+    isize::max((a.x - b.x).abs(), (a.y - b.y).abs())
+}
+
+// Whether a minor piece standing on from attacks to, with the only piece in the way possibly being at blocker
+fn minor_piece_attacks(board: &Board, model: PieceModel, from: Coords, to: Coords, blocker: Option<Coords>) -> bool {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    match model {
+        Knight => matches!((dx.abs(), dy.abs()), (1, 2) | (2, 1)),
+        Bishop => {
+            if dx == 0 || dx.abs() != dy.abs() {
+                return false;
+            }
+            (1..dx.abs()).all(|step| {
+                let c = Coords {
+                    x: from.x + dx.signum() * step,
+                    y: from.y + dy.signum() * step,
+                };
+                Some(c) != blocker && matches!(board.spaces.get(c), Some(Square { .. }))
+            })
+        }
+        _ => false,
+    }
+}
+
+// Searches the board geometry for a king + minor piece vs lone king checkmate
+// Only the attacking king's placements within reach of the mated king's flight squares need to be tried,
+// any farther placement covers nothing
+fn mating_pattern_exists(board: &Board, model: PieceModel) -> bool {
+    let is_square = |c: Coords| matches!(board.spaces.get(c), Some(Square { .. }));
+    let square_count = board.spaces.iter().filter(|space| matches!(space, Square { .. })).count();
+
+    board.spaces.indexed_iter().any(|((x, y), space)| {
+        if !matches!(space, Square { .. }) {
+            return false;
+        }
+        let mated = Coords { x: x as isize, y: y as isize };
+
+        let around = |distance: isize| {
+            (-distance..=distance)
+                .flat_map(move |dx| (-distance..=distance).map(move |dy| Coords { x: mated.x + dx, y: mated.y + dy }))
+                .filter(move |&c| chebyshev_distance(c, mated) == distance && is_square(c))
+        };
+        let flights: Vec<Coords> = around(1).collect();
+        let mut king_placements: Vec<Option<Coords>> = around(2).map(Some).collect();
+        if square_count > 1 + flights.len() + king_placements.len() {
+            king_placements.push(None);
+        }
+
+        let minor_placements: Vec<Coords> = match model {
+            Knight => [[1, 2], [2, 1], [2, -1], [1, -2], [-1, -2], [-2, -1], [-2, 1], [-1, 2isize]]
+                .into_iter()
+                .map(|[dx, dy]| Coords { x: mated.x + dx, y: mated.y + dy })
+                .filter(|&c| is_square(c))
+                .collect(),
+            _ => [[1, 1], [1, -1], [-1, -1], [-1, 1isize]]
+                .into_iter()
+                .flat_map(|[dx, dy]| {
+                    (1..)
+                        .map(move |step| Coords { x: mated.x + dx * step, y: mated.y + dy * step })
+                        .take_while(|&c| is_square(c))
+                })
+                .collect(),
+        };
+
+        king_placements.into_iter().any(|king| {
+            let covered_by_king = |c: Coords| king.is_some_and(|k| chebyshev_distance(k, c) <= 1);
+
+            minor_placements.iter().any(|&minor| {
+                Some(minor) != king
+                    && minor_piece_attacks(board, model, minor, mated, king)
+                    && flights.iter().all(|&flight| {
+                        if flight == minor {
+                            // The mated king could take the checking piece unless it is protected
+                            covered_by_king(flight)
+                        } else {
+                            covered_by_king(flight) || minor_piece_attacks(board, model, minor, flight, king)
+                        }
+                    })
             })
         })
-        .not()
+    })
 }
 
+// Moves of the side to play, keyed by source coords
+// With filter_checks, only keeps moves that don't leave one of its kings attacked. Castling additionally requires
+// the king not to be in check and every square between the king and the rook to be safe for the king
 pub fn compute_possible_moves(board: &Board, filter_checks: bool) -> HashMap<Coords, Vec<Move>> {
+    let mut possible_moves = compute_pseudo_legal_moves(board);
+    if !filter_checks {
+        return possible_moves;
+    }
+
+    let mover = board.side;
+    let kings = king_coords(board, mover);
+    let in_check = kings.iter().any(|&coords| is_square_attacked(board, coords, mover.other()));
+
+    // Moves are tried in place on a single copy of the board
+    let mut scratch = board.clone();
+    let mut keeps_kings_safe = |move_: &Move| {
+        let undo = make_move(&mut scratch, move_);
+        let safe = kings
+            .iter()
+            .map(|&coords| if coords == move_.source { move_.target } else { coords })
+            .all(|coords| !is_square_attacked(&scratch, coords, mover.other()));
+        unmake_move(&mut scratch, undo);
+        safe
+    };
+
+    for moves in possible_moves.values_mut() {
+        moves.retain(|move_| match move_.kind {
+            MoveKind::Castle { rook_coords } => {
+                let x_dir = (rook_coords.x - move_.source.x).signum();
+                !in_check
+                    && (1..(rook_coords.x - move_.source.x).abs()).all(|offset| {
+                        keeps_kings_safe(&Move {
+                            source: move_.source,
+                            target: Coords {
+                                x: move_.source.x + x_dir * offset,
+                                y: move_.source.y,
+                            },
+                            kind: MoveKind::Standard,
+                            promotion: None,
+                        })
+                    })
+                    && keeps_kings_safe(move_)
+            }
+            _ => keeps_kings_safe(move_),
+        });
+    }
+
+    possible_moves
+}
+
+fn compute_pseudo_legal_moves(board: &Board) -> HashMap<Coords, Vec<Move>> {
     board
         .spaces
         .indexed_iter()
@@ -321,10 +686,6 @@ pub fn compute_possible_moves(board: &Board, filter_checks: bool) -> HashMap<Coo
                 })
             };
 
-            let filter_checks_fn = |move_: &Move| {
-                return !filter_checks || !is_in_check_after_move(board, move_);
-            };
-
             (
                 coords,
                 // Pattern matching to build a vec of valid moves depending on source piece type
@@ -345,7 +706,7 @@ pub fn compute_possible_moves(board: &Board, filter_checks: bool) -> HashMap<Coo
                         .filter_map(move_from_target_fn)
                         // Add castling moves
                         .chain([-1, 1isize].into_iter().filter_map(|x_dir| {
-                            if !can_castle || !filter_checks_fn(&Move::skip()) {
+                            if !can_castle {
                                 return None;
                             }
                             let mut rook_offset = 1;
@@ -356,16 +717,7 @@ pub fn compute_possible_moves(board: &Board, filter_checks: bool) -> HashMap<Coo
                                 };
                                 match board.spaces.get(rook_coords) {
                                     Some(Square { slot: None, .. }) => {
-                                        if filter_checks_fn(&Move {
-                                            source: coords,
-                                            target: rook_coords,
-                                            kind: MoveKind::Standard,
-                                            promotion: None,
-                                        }) {
-                                            rook_offset += 1;
-                                        } else {
-                                            return None;
-                                        }
+                                        rook_offset += 1;
                                     }
                                     Some(Square {
                                         slot:
@@ -374,20 +726,16 @@ pub fn compute_possible_moves(board: &Board, filter_checks: bool) -> HashMap<Coo
                                                 model: Rook { can_castle: true },
                                             }),
                                         ..
-                                    }) => {
-                                        if rook_offset > 2 && board.side == *rook_side {
-                                            return Some(Move {
-                                                source: coords,
-                                                target: Coords {
-                                                    x: x + x_dir * 2,
-                                                    y,
-                                                },
-                                                kind: MoveKind::Castle { rook_coords },
-                                                promotion: None,
-                                            });
-                                        } else {
-                                            return None;
-                                        }
+                                    }) if rook_offset > 2 && board.side == *rook_side => {
+                                        return Some(Move {
+                                            source: coords,
+                                            target: Coords {
+                                                x: x + x_dir * 2,
+                                                y,
+                                            },
+                                            kind: MoveKind::Castle { rook_coords },
+                                            promotion: None,
+                                        });
                                     }
                                     _ => {
                                         return None;
@@ -395,7 +743,6 @@ pub fn compute_possible_moves(board: &Board, filter_checks: bool) -> HashMap<Coo
                                 }
                             }
                         }))
-                        .filter(filter_checks_fn)
                         .collect()
                     }
                     Queen => {
@@ -412,22 +759,16 @@ pub fn compute_possible_moves(board: &Board, filter_checks: bool) -> HashMap<Coo
                         ]
                         .into_iter()
                         // Expand directions until pieces are encountered
-                        .map(moves_from_direction_fn)
-                        .flatten()
-                        .filter(filter_checks_fn)
+                        .flat_map(moves_from_direction_fn)
                         .collect()
                     }
                     Bishop => [[-1, 1], [1, 1], [-1, -1], [1, -1isize]]
                         .into_iter()
-                        .map(moves_from_direction_fn)
-                        .flatten()
-                        .filter(filter_checks_fn)
+                        .flat_map(moves_from_direction_fn)
                         .collect(),
                     Rook { .. } => [[-1, 0], [0, 1], [0, -1], [1, 0isize]]
                         .into_iter()
-                        .map(moves_from_direction_fn)
-                        .flatten()
-                        .filter(filter_checks_fn)
+                        .flat_map(moves_from_direction_fn)
                         .collect(),
                     Knight => [
                         Coords { x: x - 1, y: y + 2 },
@@ -441,7 +782,6 @@ pub fn compute_possible_moves(board: &Board, filter_checks: bool) -> HashMap<Coo
                     ]
                     .into_iter()
                     .filter_map(move_from_target_fn)
-                    .filter(filter_checks_fn)
                     .collect(),
                     Pawn { can_dash, .. } => {
                         let mut moves = vec![];
@@ -558,10 +898,50 @@ pub fn compute_possible_moves(board: &Board, filter_checks: bool) -> HashMap<Coo
                             }
                         }
 
-                        moves.into_iter().filter(filter_checks_fn).collect()
+                        moves
                     }
                 },
             )
         })
         .collect()
 }
+
+// Number of leaf nodes of the legal move tree at a given depth, used to check the move generator against known counts
+pub fn perft(board: &Board, depth: u32) -> u64 {
+    perft_in_place(&mut board.clone(), depth)
+}
+
+fn perft_in_place(board: &mut Board, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let possible_moves = compute_possible_moves(board, true);
+    if depth == 1 {
+        return possible_moves.values().map(|moves| moves.len() as u64).sum();
+    }
+
+    possible_moves
+        .values()
+        .flatten()
+        .map(|move_| {
+            let undo = make_move(board, move_);
+            let nodes = perft_in_place(board, depth - 1);
+            unmake_move(board, undo);
+            nodes
+        })
+        .sum()
+}
+
+// Perft node counts below each legal move of the side to play, to narrow down move generation discrepancies
+// Moves are sorted by source then target coordinates
+pub fn perft_divide(board: &Board, depth: u32) -> Vec<(Move, u64)> {
+    let mut divide: Vec<(Move, u64)> = compute_possible_moves(board, true)
+        .into_values()
+        .flatten()
+        .map(|move_| (move_, perft(&get_next_board(board, &move_), depth.saturating_sub(1))))
+        .collect();
+
+    divide.sort_by_key(|(move_, _)| (move_.source.x, move_.source.y, move_.target.x, move_.target.y));
+    divide
+}
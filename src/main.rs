@@ -1,17 +1,22 @@
-mod board;
+
 mod ui;
-mod logic;
-mod turns;
+mod display;
+mod ai_player;
+mod history;
 #[cfg(target_family = "wasm")]
 mod io_wasm;
 #[cfg(not(target_family = "wasm"))]
 mod io_standard;
 
-use board::*;
+use chess2::board::*;
+use chess2::logic::*;
+use chess2::pgn::*;
+use chess2::turns::*;
+use chess2::validation::*;
 use ui::*;
-use logic::*;
+use ai_player::*;
+use history::*;
 use bevy::{asset::AssetMetaCheck, prelude::*};
-use turns::*;
 #[cfg(target_family = "wasm")]
 use io_wasm::*;
 #[cfg(not(target_family = "wasm"))]
@@ -19,36 +24,130 @@ use io_standard::*;
 
 fn setup_initial_board(
     mut turns: ResMut<Turns>,
-    mut display_state: ResMut<BoardDisplayState>
+    mut display_state: ResMut<BoardDisplayState>,
+    mut ai_players: ResMut<AIPlayers>,
+    mut animation_settings: ResMut<AnimationSettings>
 ) {
-    let initial_board = match Board::from_strings(&get_pieces_string(), &get_promotions_string()) {
-        Ok(board) => board,
-        Err(err_str) => { alert(err_str); return; }
+    let (pieces_string, promotions_string) = (get_pieces_string(), get_promotions_string());
+
+    // A single line without promotions is a full position, in FEN when its second field is the side to move,
+    // otherwise in board notation
+    let single_line = promotions_string.trim().is_empty() && pieces_string.trim().lines().count() == 1;
+    let is_fen = single_line && matches!(pieces_string.split_whitespace().nth(1), Some("w" | "b"));
+    // FEN positions keep their clocks, so that games continued from elsewhere keep their move numbers
+    let parsed_turn = if is_fen {
+        Turn::from_fen(&pieces_string).map_err(String::from)
+    } else if single_line {
+        Board::from_notation(&pieces_string)
+            .map(|board| Turn::new(board, Move::skip()))
+            .map_err(String::from)
+    } else {
+        if get_strict_layout() {
+            Board::from_strings_strict(&pieces_string, &promotions_string)
+        } else {
+            Board::from_strings(&pieces_string, &promotions_string)
+        }
+        .map(|board| Turn::new(board, Move::skip()))
+        .map_err(|err| err.to_string())
+    };
+
+    let initial_turn = match parsed_turn {
+        Ok(turn) => turn,
+        Err(err_str) => { alert(&err_str); return; }
     };
+    let issues = validate_board(&initial_turn.board);
+    let report = issues.iter().map(|issue| issue.describe(&initial_turn.board)).collect::<Vec<_>>().join("\n");
+
+    // Warnings alone don't prevent playing, and some shipped layouts trigger them on purpose,
+    // so they are shown next to the layout instead of interrupting the restart
+    if issues.iter().any(|issue| issue.severity() == Severity::Error) {
+        alert(&report);
+        return;
+    }
+    set_layout_warnings(&report);
+
+    *turns = Turns::new(initial_turn);
 
-    turns.history.clear();
-    turns.history.push_back(Turn {
-        possible_moves: compute_possible_moves(&initial_board, true),
-        board: initial_board,
-        previous_move: Move {
-            kind: MoveKind::Skip,
-            ..default()
-        },
-    });
+    // The previous game's search must not play into the new one
+    ai_players.cancel();
+    ai_players.sides = [get_white_player(), get_black_player()].map(|player| player.to_lowercase() == "computer");
+
+    animation_settings.speed = get_animation_speed().parse().unwrap_or(0.);
 
     *display_state = BoardDisplayState {
-        displayed_turn: 0,
         bottom_side: match get_bottom_side().to_lowercase().as_str() { "white" => Side::White, _ => Side::Black },
-        ..default()
     };
 }
 
 fn poll_io(
-    turns: ResMut<Turns>,
-    display_state: ResMut<BoardDisplayState>
+    mut turns: ResMut<Turns>,
+    display_state: ResMut<BoardDisplayState>,
+    mut ai_players: ResMut<AIPlayers>,
+    animation_settings: ResMut<AnimationSettings>
 ) {
+    if poll_export() {
+        // Save the displayed position as a new starting layout
+        if let Some(Turn { board, .. }) = turns.current() {
+            let (pieces_string, promotions_string) = board.to_strings();
+            set_layout_strings(&pieces_string, &promotions_string);
+        }
+    }
+
+    if poll_game_export() {
+        set_game_record(&write_pgn(&turns));
+    }
+
+    if poll_game_import() {
+        match read_pgn(&get_game_record()) {
+            Ok(read_turns) => {
+                ai_players.cancel();
+                *turns = read_turns;
+            }
+            Err(error) => alert(&format!("Could not load the game: {}", error)),
+        }
+    }
+
     if poll_restart() {
-        setup_initial_board(turns, display_state);
+        setup_initial_board(turns, display_state, ai_players, animation_settings);
+    }
+}
+
+// Shows the notation of the displayed position so that it can be copied and shared,
+// along with its FEN when it fits the standard rules
+fn export_position(
+    turns: Res<Turns>
+) {
+    if !turns.is_changed() { return; }
+
+    if let Some(turn) = turns.current() {
+        set_position_notation(&turn.board.to_notation());
+        set_position_fen(&turn.to_fen().unwrap_or_default());
+    }
+}
+
+// Notifies the players once a newly played turn ends the game
+fn report_outcome(
+    turns: Res<Turns>,
+    mut reported_turns: Local<usize>
+) {
+    if !turns.is_changed() { return; }
+
+    // Going back to a turn already in the tree doesn't notify again
+    let new_turn = turns.len() > *reported_turns;
+    *reported_turns = turns.len();
+    if !new_turn { return; }
+
+    let Some(Turn { outcome, .. }) = turns.current() else { return };
+
+    match outcome {
+        GameOutcome::Checkmate { winner } => alert(&format!("Checkmate! {:?} wins.", winner)),
+        GameOutcome::Stalemate => alert("Stalemate!"),
+        GameOutcome::Draw { reason } => alert(&format!("Draw: {}.", match reason {
+            DrawReason::FiftyMoveRule => "fifty-move rule",
+            DrawReason::ThreefoldRepetition => "threefold repetition",
+            DrawReason::InsufficientMaterial => "insufficient material",
+        })),
+        GameOutcome::Ongoing => (),
     }
 }
 
@@ -68,10 +167,12 @@ pub fn main() {
                 }
             ),
             UIPlugin,
+            AIPlugin,
+            HistoryPlugin,
             LogicPlugin,
             TurnsPlugin
         ))
         .add_systems(PreStartup, setup_initial_board)
-        .add_systems(Update, poll_io)
+        .add_systems(Update, (poll_io, export_position, report_outcome))
         .run();
 }
@@ -0,0 +1,245 @@
+use std::fmt;
+
+use crate::board::PieceModel::*;
+use crate::board::*;
+use crate::logic::*;
+
+// Files are named like spreadsheet columns, so that any width can be named: a..z, aa..az, ba..
+// Ranks are numbered from 1, from the bottom of the board
+pub fn file_name(x: isize) -> String {
+    let mut name = vec![];
+    let mut remaining = x + 1;
+    while remaining > 0 {
+        remaining -= 1;
+        name.push(b'a' + (remaining % 26) as u8);
+        remaining /= 26;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap()
+}
+
+pub fn rank_name(y: isize) -> String {
+    (y + 1).to_string()
+}
+
+pub fn square_name(coords: Coords) -> String {
+    format!("{}{}", file_name(coords.x), rank_name(coords.y))
+}
+
+fn parse_file(name: &str) -> Option<isize> {
+    if name.is_empty() || !name.bytes().all(|c| c.is_ascii_lowercase()) {
+        return None;
+    }
+    name.bytes()
+        .try_fold(0isize, |x, c| x.checked_mul(26)?.checked_add((c - b'a') as isize + 1))
+        .map(|x| x - 1)
+}
+
+fn parse_rank(name: &str) -> Option<isize> {
+    if name.is_empty() || !name.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    name.parse::<isize>().ok().filter(|&rank| rank > 0).map(|rank| rank - 1)
+}
+
+// Reads a square name such as "e4" or "ab12", regardless of the board's size
+pub fn parse_square(name: &str) -> Option<Coords> {
+    let split = name.find(|c: char| c.is_ascii_digit())?;
+    let (file, rank) = name.split_at(split);
+    Some(Coords {
+        x: parse_file(file)?,
+        y: parse_rank(rank)?,
+    })
+}
+
+fn model_letter(model: PieceModel) -> Option<char> {
+    match model {
+        King { .. } => Some('K'),
+        Queen => Some('Q'),
+        Bishop => Some('B'),
+        Knight => Some('N'),
+        Rook { .. } => Some('R'),
+        Pawn { .. } => None,
+    }
+}
+
+fn piece_at(board: &Board, coords: Coords) -> Option<Piece> {
+    match board.spaces.get(coords) {
+        Some(Space::Square { slot, .. }) => *slot,
+        _ => None,
+    }
+}
+
+fn same_model(a: PieceModel, b: PieceModel) -> bool {
+    std::mem::discriminant(&a) == std::mem::discriminant(&b)
+}
+
+// How much of the source square a move needs to be told apart from the others
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Disambiguation {
+    None,
+    File,
+    Rank,
+    Square,
+}
+
+// Writes a move without disambiguation rules or check suffix, the base of both move_to_san and parse_san
+fn write_move(board: &Board, move_: &Move, disambiguation: Disambiguation, castle_prefix: bool) -> String {
+    let mut san = String::new();
+
+    if let MoveKind::Castle { rook_coords } = move_.kind {
+        if castle_prefix {
+            san.push_str(&square_name(move_.source));
+        }
+        san.push_str(if rook_coords.x > move_.source.x { "O-O" } else { "O-O-O" });
+        return san;
+    }
+    if move_.kind == MoveKind::Skip {
+        return String::from("--");
+    }
+
+    let Some(piece) = piece_at(board, move_.source) else { return san };
+
+    if let Some(letter) = model_letter(piece.model) {
+        san.push(letter);
+    }
+    match disambiguation {
+        Disambiguation::None => (),
+        Disambiguation::File => san.push_str(&file_name(move_.source.x)),
+        Disambiguation::Rank => san.push_str(&rank_name(move_.source.y)),
+        Disambiguation::Square => san.push_str(&square_name(move_.source)),
+    }
+    if matches!(move_.kind, MoveKind::Capture | MoveKind::EnPassant) {
+        san.push('x');
+    }
+    san.push_str(&square_name(move_.target));
+    if let Some(letter) = move_.promotion.and_then(model_letter) {
+        san.push('=');
+        san.push(letter);
+    }
+
+    san
+}
+
+fn legal_moves(board: &Board) -> Vec<Move> {
+    compute_possible_moves(board, true).into_values().flatten().collect()
+}
+
+// Past 26 files, file names have several letters, and a file written right before the target's file
+// can't be told apart from it, as in Qad4 for Qa1 to d4 or Qad1 to ad4
+// The capture mark separates them, otherwise the whole source square is written instead
+fn file_disambiguation_allowed(board: &Board, move_: &Move) -> bool {
+    board.spaces.dim().0 <= 26 || matches!(move_.kind, MoveKind::Capture | MoveKind::EnPassant)
+}
+
+// Shortest disambiguation telling a move apart from the moves of similar pieces to the same square
+fn disambiguation(board: &Board, move_: &Move, moves: &[Move]) -> Disambiguation {
+    let Some(piece) = piece_at(board, move_.source) else { return Disambiguation::None };
+
+    let rivals: Vec<Coords> = moves
+        .iter()
+        .filter(|other| {
+            other.source != move_.source
+                && other.target == move_.target
+                && other.promotion == move_.promotion
+                && !matches!(other.kind, MoveKind::Castle { .. })
+                && piece_at(board, other.source).is_some_and(|other_piece| same_model(other_piece.model, piece.model))
+        })
+        .map(|other| other.source)
+        .collect();
+
+    // Pawn captures always name the file they come from
+    let pawn_capture = matches!(piece.model, Pawn { .. }) && matches!(move_.kind, MoveKind::Capture | MoveKind::EnPassant);
+
+    if rivals.is_empty() && !pawn_capture {
+        Disambiguation::None
+    } else if rivals.iter().all(|rival| rival.x != move_.source.x) && file_disambiguation_allowed(board, move_) {
+        Disambiguation::File
+    } else if rivals.iter().all(|rival| rival.y != move_.source.y) && !pawn_capture {
+        Disambiguation::Rank
+    } else {
+        Disambiguation::Square
+    }
+}
+
+// Whether another king of the same side can castle in the same direction
+fn castle_needs_prefix(move_: &Move, moves: &[Move]) -> bool {
+    let MoveKind::Castle { rook_coords } = move_.kind else { return false };
+    let direction = (rook_coords.x - move_.source.x).signum();
+
+    moves.iter().any(|other| match other.kind {
+        MoveKind::Castle { rook_coords: other_rook } => {
+            other.source != move_.source && (other_rook.x - other.source.x).signum() == direction
+        }
+        _ => false,
+    })
+}
+
+// "+" if the move gives check, "#" if it also leaves no legal move
+fn check_suffix(board: &Board, move_: &Move) -> &'static str {
+    let next_board = get_next_board(board, move_);
+    if !is_in_check(&next_board) {
+        ""
+    } else if legal_moves(&next_board).is_empty() {
+        "#"
+    } else {
+        "+"
+    }
+}
+
+// Writes a legal move of a board in standard algebraic notation, using the square names of file_name and rank_name
+// Castling is written O-O towards higher files and O-O-O towards lower ones,
+// preceded by the king's square when several kings could castle in that direction
+pub fn move_to_san(board: &Board, move_: &Move) -> String {
+    let moves = legal_moves(board);
+    let san = write_move(board, move_, disambiguation(board, move_, &moves), castle_needs_prefix(move_, &moves));
+    san + check_suffix(board, move_)
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum NotationError {
+    Empty,
+    NoMatchingMove { san: String },
+    AmbiguousMove { san: String },
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NotationError::Empty => write!(f, "Move notation is empty"),
+            NotationError::NoMatchingMove { san } => write!(f, "No legal move matches '{}'", san),
+            NotationError::AmbiguousMove { san } => write!(f, "Several legal moves match '{}'", san),
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+// Reads a move in the notation of move_to_san, returning the legal move it designates
+// Check suffixes and annotations are ignored, and pieces may be disambiguated more than necessary
+pub fn parse_san(board: &Board, san: &str) -> Result<Move, NotationError> {
+    let stripped = san.trim().trim_end_matches(['!', '?', '+', '#']);
+    if stripped.is_empty() {
+        return Err(NotationError::Empty);
+    }
+    // Zeros are accepted in castling, where they stand for the letter O
+    let stripped = if stripped.ends_with("0-0") { stripped.replace('0', "O") } else { stripped.to_string() };
+
+    // Every way of writing each legal move is tried, so that too little disambiguation is reported as such,
+    // except file disambiguations that would merge with the target's file
+    let moves = legal_moves(board);
+    let mut matches = moves.iter().filter(|move_| {
+        [Disambiguation::None, Disambiguation::File, Disambiguation::Rank, Disambiguation::Square]
+            .into_iter()
+            .filter(|&level| level != Disambiguation::File || file_disambiguation_allowed(board, move_))
+            .any(|level| [false, true].into_iter().any(|prefix| write_move(board, move_, level, prefix) == stripped))
+    });
+
+    let Some(&move_) = matches.next() else {
+        return Err(NotationError::NoMatchingMove { san: san.to_string() });
+    };
+    if matches.next().is_some() {
+        return Err(NotationError::AmbiguousMove { san: san.to_string() });
+    }
+    Ok(move_)
+}
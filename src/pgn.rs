@@ -0,0 +1,284 @@
+use std::fmt;
+
+use crate::board::*;
+use crate::logic::*;
+use crate::notation::*;
+use crate::turns::*;
+
+// Position notation of the standard chess starting position, for which no layout tag is written
+pub const STANDARD_LAYOUT: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR WWWWWWWW/8/8/8/8/8/8/bbbbbbbb w -";
+// Tag holding the position notation of the initial board of games starting from any other position
+pub const LAYOUT_TAG: &str = "Layout";
+// Standard tags giving the initial position of games that other PGN tools can read, when it has a FEN
+pub const SETUP_TAG: &str = "SetUp";
+pub const FEN_TAG: &str = "FEN";
+
+// Movetext lines are kept under the length recommended by the PGN standard
+const MAX_LINE_LENGTH: usize = 79;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PgnError {
+    InvalidTag { line: usize },
+    InvalidLayout { message: String },
+    InvalidFen { message: String },
+    InvalidMove { san: String, error: NotationError },
+    UnbalancedVariation,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::InvalidTag { line } => write!(f, "Line {}: invalid tag pair", line),
+            PgnError::InvalidLayout { message } => write!(f, "Invalid {} tag: {}", LAYOUT_TAG, message),
+            PgnError::InvalidFen { message } => write!(f, "Invalid {} tag: {}", FEN_TAG, message),
+            PgnError::InvalidMove { san, error } => write!(f, "Invalid move '{}': {}", san, error),
+            PgnError::UnbalancedVariation => write!(f, "Unbalanced variation parentheses"),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+fn result_string(outcome: GameOutcome) -> &'static str {
+    match outcome {
+        GameOutcome::Checkmate { winner: Side::White } => "1-0",
+        GameOutcome::Checkmate { winner: Side::Black } => "0-1",
+        GameOutcome::Stalemate | GameOutcome::Draw { .. } => "1/2-1/2",
+        GameOutcome::Ongoing => "*",
+    }
+}
+
+// Number of the move leading from a turn, going on from the fullmove number of the initial turn
+fn move_number(turns: &Turns, id: TurnId) -> u32 {
+    turns.get(id).map_or(1, |turn| turn.fullmove_number)
+}
+
+// Writes the moves following a turn, its first child's move then its variations, then the rest of the mainline
+// Move numbers are written before White's moves, and before Black's when they start a line or follow a variation
+fn write_continuation(turns: &Turns, id: TurnId, tokens: &mut Vec<String>, force_number: bool) {
+    let Some(board) = turns.get(id).map(|turn| &turn.board) else { return };
+    let children = turns.children(id);
+    let Some(&main_child) = children.first() else { return };
+
+    let move_token = |child: TurnId, force_number: bool| {
+        let san = turns.get(child).map_or(String::new(), |turn| move_to_san(board, &turn.previous_move));
+        match board.side {
+            Side::White => format!("{}. {}", move_number(turns, id), san),
+            Side::Black if force_number => format!("{}... {}", move_number(turns, id), san),
+            Side::Black => san,
+        }
+    };
+
+    tokens.push(move_token(main_child, force_number));
+    for &variation in &children[1..] {
+        tokens.push(format!("({}", move_token(variation, true)));
+        write_continuation(turns, variation, tokens, false);
+        if let Some(last) = tokens.last_mut() {
+            last.push(')');
+        }
+    }
+
+    write_continuation(turns, main_child, tokens, children.len() > 1);
+}
+
+// Writes the whole game tree in PGN, variations included
+// The result is that of the last mainline turn, and games not starting from the standard position get a layout tag,
+// along with FEN tags when their initial board fits the standard rules
+pub fn write_pgn(turns: &Turns) -> String {
+    let Some(initial_turn) = turns.get(0) else { return String::new() };
+
+    let mainline_end = turns.mainline().last().copied().unwrap_or(0);
+    let result = turns.get(mainline_end).map_or("*", |turn| result_string(turn.outcome));
+
+    let mut tags = vec![
+        ("Event", String::from("Casual game")),
+        ("Site", String::from("chess2")),
+        ("Date", String::from("????.??.??")),
+        ("Round", String::from("-")),
+        ("White", String::from("?")),
+        ("Black", String::from("?")),
+        ("Result", String::from(result)),
+    ];
+    let is_standard = initial_turn.halfmove_clock == 0
+        && initial_turn.fullmove_number == 1
+        && Board::from_notation(STANDARD_LAYOUT).is_ok_and(|standard| standard == initial_turn.board);
+    if !is_standard {
+        tags.push((LAYOUT_TAG, initial_turn.board.to_notation()));
+        if let Ok(fen) = initial_turn.to_fen() {
+            tags.push((SETUP_TAG, String::from("1")));
+            tags.push((FEN_TAG, fen));
+        }
+    }
+
+    let mut pgn: String = tags
+        .into_iter()
+        .map(|(name, value)| format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect();
+    pgn.push('\n');
+
+    let mut tokens = vec![];
+    write_continuation(turns, 0, &mut tokens, true);
+    tokens.push(String::from(result));
+
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    pgn.push_str(&line);
+    pgn.push('\n');
+
+    pgn
+}
+
+// Reads a tag pair line such as [Event "Casual game"]
+fn parse_tag(line: &str) -> Option<(&str, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?.trim();
+    let (name, value) = inner.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        unescaped.push(if c == '\\' { chars.next()? } else { c });
+    }
+    Some((name, unescaped))
+}
+
+#[derive(Debug)]
+enum Token<'a> {
+    Move(&'a str),
+    VariationStart,
+    VariationEnd,
+}
+
+// Splits movetext into moves and variation parentheses, dropping comments, move numbers, annotation glyphs and results
+fn tokenize(movetext: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    let mut rest = movetext;
+
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '{' => rest = rest.find('}').map_or("", |end| &rest[end + 1..]),
+            ';' => rest = rest.find('\n').map_or("", |end| &rest[end + 1..]),
+            '(' | ')' => {
+                tokens.push(if c == '(' { Token::VariationStart } else { Token::VariationEnd });
+                rest = &rest[1..];
+            }
+            _ if c.is_whitespace() => rest = &rest[c.len_utf8()..],
+            _ => {
+                let end = rest.find(|c: char| c.is_whitespace() || "{;()".contains(c)).unwrap_or(rest.len());
+                let word = &rest[..end];
+                rest = &rest[end..];
+
+                if matches!(word, "1-0" | "0-1" | "1/2-1/2" | "*") || word.starts_with('$') {
+                    continue;
+                }
+                // Move numbers end with dots, and may be glued to the move they number, as in "1.e4"
+                let word = word.rsplit_once('.').map_or(word, |(_, move_)| move_);
+                if !word.is_empty() {
+                    tokens.push(Token::Move(word));
+                }
+            }
+        }
+    }
+
+    tokens
+}
+
+// Reads a game written by write_pgn or by other PGN tools, variations included
+// Only the first game of the text is read, and the initial board is given by the layout tag if there is one,
+// otherwise by the FEN tag unless a SetUp tag disables it
+// The clocks come from the FEN tag, so that moves are numbered from its fullmove number
+pub fn read_pgn(pgn: &str) -> Result<Turns, PgnError> {
+    let mut layout = None;
+    let mut fen = None;
+    let mut setup = true;
+    let mut movetext_start = 0;
+
+    for (index, line) in pgn.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if !trimmed.starts_with('[') {
+            movetext_start = index;
+            break;
+        }
+        let Some((name, value)) = parse_tag(trimmed) else {
+            return Err(PgnError::InvalidTag { line: index + 1 });
+        };
+        match name {
+            LAYOUT_TAG => layout = Some(value),
+            FEN_TAG => fen = Some(value),
+            SETUP_TAG => setup = value != "0",
+            _ => (),
+        }
+        movetext_start = index + 1;
+    }
+
+    let (fen_board, halfmove_clock, fullmove_number) = match fen.filter(|_| setup) {
+        Some(fen) => {
+            let (board, halfmove_clock, fullmove_number) = Board::from_fen_with_clocks(&fen)
+                .map_err(|message| PgnError::InvalidFen { message: String::from(message) })?;
+            (Some(board), halfmove_clock, fullmove_number)
+        }
+        None => (None, 0, 1),
+    };
+    let initial_board = match (layout, fen_board) {
+        (Some(layout), _) => Board::from_notation(&layout)
+            .map_err(|message| PgnError::InvalidLayout { message: String::from(message) })?,
+        (None, Some(board)) => board,
+        (None, None) => Board::from_notation(STANDARD_LAYOUT)
+            .map_err(|message| PgnError::InvalidLayout { message: String::from(message) })?,
+    };
+    let mut initial_turn = Turn::new(initial_board, Move::skip());
+    initial_turn.halfmove_clock = halfmove_clock;
+    initial_turn.fullmove_number = fullmove_number;
+    let mut turns = Turns::new(initial_turn);
+
+    // Turns to come back to at the end of each variation being read
+    let mut variation_stack = vec![];
+    // The tags of a following game end the movetext
+    let movetext: Vec<&str> = pgn
+        .lines()
+        .skip(movetext_start)
+        .take_while(|line| !line.trim_start().starts_with('['))
+        .collect();
+
+    for token in tokenize(&movetext.join("\n")) {
+        match token {
+            Token::Move(san) => {
+                let board = &turns.current().ok_or(PgnError::UnbalancedVariation)?.board;
+                let move_ = parse_san(board, san).map_err(|error| PgnError::InvalidMove { san: String::from(san), error })?;
+                turns.push_move(&move_);
+            }
+            // A variation replaces the move just read
+            Token::VariationStart => {
+                let current = turns.current_id();
+                let parent = turns.parent(current).ok_or(PgnError::UnbalancedVariation)?;
+                variation_stack.push(current);
+                turns.go_to(parent);
+            }
+            Token::VariationEnd => {
+                let resumed = variation_stack.pop().ok_or(PgnError::UnbalancedVariation)?;
+                turns.go_to(resumed);
+            }
+        }
+    }
+
+    if !variation_stack.is_empty() {
+        return Err(PgnError::UnbalancedVariation);
+    }
+
+    let mainline_end = turns.mainline().last().copied().unwrap_or(0);
+    turns.go_to(mainline_end);
+    Ok(turns)
+}
@@ -1,13 +1,18 @@
 use std::collections::HashMap;
-use std::collections::VecDeque;
 
+#[cfg(feature = "bevy")]
 use bevy::prelude::*;
 
 use crate::board::*;
 use crate::logic::*;
 
+const FIFTY_MOVE_RULE_HALFMOVES: u32 = 100;
+const REPETITIONS_FOR_DRAW: usize = 3;
+
+#[cfg(feature = "bevy")]
 pub struct TurnsPlugin;
 
+#[cfg(feature = "bevy")]
 impl Plugin for TurnsPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Turns::default());
@@ -19,10 +24,290 @@ pub struct Turn {
     pub previous_move: Move,
     pub board: Board,
     pub possible_moves: HashMap<Coords, Vec<Move>>,
+    pub outcome: GameOutcome,
+    // Halfmoves played since the last capture or pawn move
+    pub halfmove_clock: u32,
+    // Number of the move played from this turn, counting from 1 and increasing after Black's moves, as in FEN
+    pub fullmove_number: u32,
+}
+
+impl Turn {
+    // Builds a turn from the board reached after previous_move, computing its legal moves and outcome
+    // History-dependent draws are not detected here, see Turns::push_move
+    pub fn new(board: Board, previous_move: Move) -> Self {
+        let possible_moves = compute_possible_moves(&board, true);
+        let outcome = compute_outcome(&board, &possible_moves);
+
+        Turn {
+            previous_move,
+            board,
+            possible_moves,
+            outcome,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+        }
+    }
+
+    // Builds an initial turn from a FEN record, keeping its clocks
+    pub fn from_fen(fen: &str) -> Result<Self, &'static str> {
+        let (board, halfmove_clock, fullmove_number) = Board::from_fen_with_clocks(fen)?;
+        let mut turn = Turn::new(board, Move::skip());
+        turn.halfmove_clock = halfmove_clock;
+        turn.fullmove_number = fullmove_number;
+        Ok(turn)
+    }
+
+    // Exports the turn as a FEN record with its clocks, if its board fits the standard rules
+    pub fn to_fen(&self) -> Result<String, &'static str> {
+        self.board.to_fen(self.halfmove_clock, self.fullmove_number)
+    }
 }
 
-// Queue of all the turns up to and including the one currently in play
-#[derive(Resource, Default)]
+// Index of a turn in the game tree
+// Ids are never reused nor renumbered, so that they can be held while turns are added and removed
+pub type TurnId = usize;
+
+struct TurnNode {
+    turn: Turn,
+    parent: Option<TurnId>,
+    // The first child continues the mainline, the others start variations
+    children: Vec<TurnId>,
+}
+
+// Tree of all the turns played from the initial one, keeping every variation
+// The current turn is the one moves are played from
+#[cfg_attr(feature = "bevy", derive(Resource))]
+#[derive(Default)]
 pub struct Turns {
-    pub history: VecDeque<Turn>
-}
\ No newline at end of file
+    // Removed turns leave an empty slot, keeping the ids of the others
+    nodes: Vec<Option<TurnNode>>,
+    removed_count: usize,
+    current: TurnId,
+    // Moves taken back by undo, the next one to replay last
+    redo_moves: Vec<Move>,
+}
+
+impl Turns {
+    // Starts a new tree from an initial turn, which becomes the current one
+    pub fn new(initial_turn: Turn) -> Self {
+        Turns {
+            nodes: vec![Some(TurnNode {
+                turn: initial_turn,
+                parent: None,
+                children: vec![],
+            })],
+            removed_count: 0,
+            current: 0,
+            redo_moves: vec![],
+        }
+    }
+
+    fn node(&self, id: TurnId) -> Option<&TurnNode> {
+        self.nodes.get(id)?.as_ref()
+    }
+
+    fn node_mut(&mut self, id: TurnId) -> Option<&mut TurnNode> {
+        self.nodes.get_mut(id)?.as_mut()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Number of turns in the tree, variations included
+    pub fn len(&self) -> usize {
+        self.nodes.len() - self.removed_count
+    }
+
+    pub fn get(&self, id: TurnId) -> Option<&Turn> {
+        self.node(id).map(|node| &node.turn)
+    }
+
+    pub fn current_id(&self) -> TurnId {
+        self.current
+    }
+
+    pub fn current(&self) -> Option<&Turn> {
+        self.get(self.current)
+    }
+
+    // Makes a turn of the tree the current one, returning false if it does not exist
+    // Moving elsewhere forgets the moves that could be redone
+    pub fn go_to(&mut self, id: TurnId) -> bool {
+        if self.node(id).is_none() {
+            return false;
+        }
+        if id != self.current {
+            self.redo_moves.clear();
+        }
+        self.current = id;
+        true
+    }
+
+    pub fn parent(&self, id: TurnId) -> Option<TurnId> {
+        self.node(id)?.parent
+    }
+
+    // Turns following a turn, the mainline continuation first
+    pub fn children(&self, id: TurnId) -> &[TurnId] {
+        self.node(id).map_or(&[], |node| &node.children)
+    }
+
+    // Turns from the initial one to the given one
+    pub fn line(&self, id: TurnId) -> Vec<TurnId> {
+        let mut line: Vec<TurnId> = std::iter::successors(Some(id).filter(|&id| self.node(id).is_some()), |&id| self.parent(id)).collect();
+        line.reverse();
+        line
+    }
+
+    // Turns from the initial one following the first child of each turn
+    pub fn mainline(&self) -> Vec<TurnId> {
+        if self.is_empty() {
+            return vec![];
+        }
+        std::iter::successors(Some(0), |&id| self.children(id).first().copied()).collect()
+    }
+
+    // Last turn reached from a turn by following the first child of each turn
+    pub fn line_end(&self, id: TurnId) -> TurnId {
+        std::iter::successors(Some(id), |&id| self.children(id).first().copied()).last().unwrap_or(id)
+    }
+
+    // Whether a turn is on the mainline, rather than in a variation
+    pub fn is_mainline(&self, id: TurnId) -> bool {
+        self.line(id).windows(2).all(|pair| self.children(pair[0]).first() == Some(&pair[1]))
+    }
+
+    // Makes the line leading to a turn the mainline, keeping the previous mainline as a variation
+    pub fn promote_variation(&mut self, id: TurnId) {
+        for pair in self.line(id).windows(2) {
+            let Some(node) = self.node_mut(pair[0]) else { continue };
+            let children = &mut node.children;
+            if let Some(index) = children.iter().position(|&child| child == pair[1]) {
+                children[..=index].rotate_right(1);
+            }
+        }
+    }
+
+    // Takes back the current turn, returning the move that led to it
+    // Only the last turn of a line can be taken back, and never the initial one
+    pub fn undo(&mut self) -> Option<Move> {
+        let undone = self.current;
+        let parent = self.parent(undone)?;
+        if !self.children(undone).is_empty() {
+            return None;
+        }
+
+        let move_ = self.get(undone)?.previous_move;
+        self.current = parent;
+        self.remove_leaf(undone);
+        self.redo_moves.push(move_);
+        Some(move_)
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_moves.is_empty()
+    }
+
+    // Plays the last move taken back by undo again, returning it
+    pub fn redo(&mut self) -> Option<Move> {
+        let move_ = self.redo_moves.pop()?;
+        self.play_move(&move_);
+        Some(move_)
+    }
+
+    // Removes a turn without children from the tree, leaving the ids of the other turns unchanged
+    // Its id is not given to later turns, so that a held id never designates another turn
+    fn remove_leaf(&mut self, id: TurnId) {
+        let Some(node) = self.nodes.get_mut(id).and_then(Option::take) else { return };
+        if let Some(parent) = node.parent.and_then(|parent| self.node_mut(parent)) {
+            parent.children.retain(|&child| child != id);
+        }
+        self.removed_count += 1;
+    }
+
+    // Plays a move from the current turn, which must exist, making the resulting turn the current one
+    // A move that was already played from there goes back to its turn instead of starting a new variation
+    // Playing a move forgets the moves that could be redone
+    pub fn push_move(&mut self, move_: &Move) {
+        self.redo_moves.clear();
+        self.play_move(move_);
+    }
+
+    fn play_move(&mut self, move_: &Move) {
+        let current_node = self.node(self.current).expect("play_move: no current turn");
+
+        if let Some(&child) = current_node
+            .children
+            .iter()
+            .find(|&&child| self.get(child).is_some_and(|turn| turn.previous_move == *move_))
+        {
+            self.current = child;
+            return;
+        }
+
+        let last_turn = &current_node.turn;
+
+        let irreversible = match move_.kind {
+            MoveKind::Capture | MoveKind::EnPassant => true,
+            MoveKind::Skip => false,
+            _ => matches!(
+                last_turn.board.spaces.get(move_.source),
+                Some(Space::Square { slot: Some(Piece { model: PieceModel::Pawn { .. }, .. }), .. })
+            ),
+        };
+
+        let mut turn = Turn::new(get_next_board(&last_turn.board, move_), *move_);
+        turn.halfmove_clock = if irreversible { 0 } else { last_turn.halfmove_clock + 1 };
+        turn.fullmove_number = last_turn.fullmove_number + u32::from(last_turn.board.side == Side::Black);
+
+        if turn.outcome == GameOutcome::Ongoing {
+            if self.repetitions(&turn) + 1 >= REPETITIONS_FOR_DRAW {
+                turn.outcome = GameOutcome::Draw { reason: DrawReason::ThreefoldRepetition };
+            } else if turn.halfmove_clock >= FIFTY_MOVE_RULE_HALFMOVES {
+                turn.outcome = GameOutcome::Draw { reason: DrawReason::FiftyMoveRule };
+            }
+        }
+
+        let id = self.nodes.len();
+        self.nodes.push(Some(TurnNode {
+            turn,
+            parent: Some(self.current),
+            children: vec![],
+        }));
+        if let Some(current_node) = self.node_mut(self.current) {
+            current_node.children.push(id);
+        }
+        self.current = id;
+    }
+
+    // Plays a move from the current turn after checking that it is one of its legal moves
+    pub fn try_push_move(&mut self, move_: &Move) -> Result<(), MoveError> {
+        let Some(current_turn) = self.current() else {
+            return Err(MoveError::Illegal);
+        };
+        if !current_turn
+            .possible_moves
+            .get(&move_.source)
+            .is_some_and(|moves| moves.contains(move_))
+        {
+            return Err(MoveError::Illegal);
+        }
+
+        self.push_move(move_);
+        Ok(())
+    }
+
+    // Number of times the position of a turn following the current one already occurred in its line
+    // Positions can only repeat since the last irreversible move, with the same side to play
+    fn repetitions(&self, turn: &Turn) -> usize {
+        std::iter::successors(Some(self.current), |&id| self.parent(id))
+            .filter_map(|id| self.get(id))
+            .take(turn.halfmove_clock as usize)
+            .skip(1)
+            .step_by(2)
+            // Hashes rule out most positions without comparing whole boards
+            .filter(|past_turn| past_turn.board.hash == turn.board.hash && past_turn.board == turn.board)
+            .count()
+    }
+}
@@ -1,11 +1,15 @@
+use std::collections::HashMap;
 use std::f32::consts::PI;
 
-use crate::board::PieceModel::*;
-use crate::board::*;
-use crate::logic::*;
-use crate::turns::*;
+use chess2::board::PieceModel::*;
+use chess2::board::*;
+use chess2::logic::*;
+use chess2::turns::*;
 use bevy::{prelude::*, transform::TransformSystem, window::WindowResized};
 
+use crate::ai_player::AIPlayers;
+use crate::display::*;
+
 const BG_TEX_SIZE: Vec2 = Vec2::new(2560., 1587.);
 const PIECE_TEX_SIZE: f32 = 256.;
 enum SpriteHeight {
@@ -25,6 +29,7 @@ impl Plugin for UIPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(BoardDisplayState::default())
             .insert_resource(Selections::default())
+            .insert_resource(AnimationSettings::default())
             .add_systems(Startup, init_ui)
             .add_systems(PostUpdate,
                 update_transform_cache
@@ -35,7 +40,9 @@ impl Plugin for UIPlugin {
                     move_piece,
                     update_board_display
                 ).chain(),
-                update_playground_transform
+                update_playground_transform,
+                update_background_tint,
+                animate_pieces
             ));
     }
 }
@@ -58,6 +65,22 @@ struct PromotionPopup;
 #[derive(Component, Deref, DerefMut)]
 struct PromotionChoice(PieceModel);
 
+// Slide of a piece from the square it left to the one it was displayed on
+#[derive(Component)]
+struct PieceAnimation {
+    from: Vec2,
+    to: Vec2,
+    elapsed: f32,
+    duration: f32,
+}
+
+// Captured piece shown fading out on the square it was taken on
+#[derive(Component)]
+struct FadingPiece {
+    elapsed: f32,
+    duration: f32,
+}
+
 // Cache of a Global Transform's inverse matrix
 // Updated automatically through change detection
 // Used to compute where the cursor is relative to a transformed entity
@@ -74,15 +97,104 @@ impl Default for InverseGTransformCache {
     }
 }
 
+// State of the mouse's left button, or of the finger moving pieces on touch screens
+struct PointerInput {
+    // Position in window coordinates, from the top-left corner
+    position: Vec2,
+    just_pressed: bool,
+    pressed: bool,
+    just_released: bool,
+    touch: bool,
+}
+
+impl PointerInput {
+    // Follows a single finger from the moment it touches the screen, and falls back on the mouse otherwise
+    fn read(
+        window: &Window,
+        buttons: &ButtonInput<MouseButton>,
+        touches: &Touches,
+        tracked_touch: &mut Option<u64>
+    ) -> Option<Self> {
+        if let Some(id) = *tracked_touch {
+            if let Some(touch) = touches.get_pressed(id) {
+                return Some(PointerInput {
+                    position: touch.position(),
+                    just_pressed: touches.just_pressed(id),
+                    pressed: true,
+                    just_released: false,
+                    touch: true,
+                });
+            }
+
+            *tracked_touch = None;
+            // A canceled touch is handled like a release
+            if let Some(touch) = touches.iter_just_released().chain(touches.iter_just_canceled()).find(|touch| touch.id() == id) {
+                return Some(PointerInput {
+                    position: touch.position(),
+                    just_pressed: false,
+                    pressed: false,
+                    just_released: true,
+                    touch: true,
+                });
+            }
+        }
+
+        if let Some(touch) = touches.iter_just_pressed().next() {
+            *tracked_touch = Some(touch.id());
+            return Some(PointerInput {
+                position: touch.position(),
+                just_pressed: true,
+                pressed: true,
+                just_released: false,
+                touch: true,
+            });
+        }
+
+        Some(PointerInput {
+            position: window.cursor_position()?,
+            just_pressed: buttons.just_pressed(MouseButton::Left),
+            pressed: buttons.pressed(MouseButton::Left),
+            just_released: buttons.just_released(MouseButton::Left),
+            touch: false,
+        })
+    }
+}
+
 #[derive(Resource, Default)]
 pub struct BoardDisplayState {
-    pub displayed_turn: usize,
     pub bottom_side: Side,
 }
 
+fn square_color(coords: Coords, highlight: Option<SquareHighlight>) -> Color {
+    let dark = (coords.x + coords.y) % 2 == 0;
+    match (highlight, dark) {
+        (None, true) => Color::rgb(0.2, 0.3, 0.4),
+        (None, false) => Color::rgb(0.8, 0.8, 0.8),
+        (Some(SquareHighlight::LastMove), true) => Color::rgb(0.45, 0.5, 0.25),
+        (Some(SquareHighlight::LastMove), false) => Color::rgb(0.85, 0.85, 0.45),
+        (Some(SquareHighlight::Check), true) => Color::rgb(0.6, 0.15, 0.15),
+        (Some(SquareHighlight::Check), false) => Color::rgb(0.9, 0.4, 0.35),
+    }
+}
+
+// Speed of the pieces moving on the board, in squares per second
+// Moves are displayed instantly when it is not positive
+#[derive(Resource)]
+pub struct AnimationSettings {
+    pub speed: f32,
+}
+
+impl Default for AnimationSettings {
+    fn default() -> Self {
+        Self { speed: 8. }
+    }
+}
+
 #[derive(Resource, Default)]
 struct Selections {
-    pub piece: Option<Entity>,
+    pub selection: Selection,
+    // Whether the last move was dropped on its target, where the piece already is and doesn't need to slide
+    pub dropped: bool,
     pub promotion: Option<Move>
 }
 
@@ -130,141 +242,288 @@ fn init_ui(
     ));
 }
 
+// Darkens the background while looking at a turn which already has a continuation,
+// where playing a move starts a variation
+fn update_background_tint(
+    mut backgrounds: Query<&mut Sprite, With<Background>>,
+    turns: Res<Turns>
+) {
+    if !turns.is_changed() { return; }
+
+    let viewing_past = !turns.children(turns.current_id()).is_empty();
+    for mut sprite in backgrounds.iter_mut() {
+        sprite.color = if viewing_past { Color::rgb(0.5, 0.5, 0.5) } else { Color::WHITE };
+    }
+}
+
+// Brings the displayed squares and pieces in line with the current board
+// Squares persist as long as the board has them, and pieces that stay on their square are kept as they are,
+// so that only the squares a move changed are touched
+// Squares are tinted to show the last move, and the kings in check of the side to move
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn update_board_display(
     mut commands: Commands,
-    mut set: ParamSet<(
-        Query<Entity, With<Playground>>,
-        Query<Entity, Or<(With<Piece>, With<Square>)>>
-    )>,
+    playground: Query<Entity, With<Playground>>,
+    mut displayed_squares: Query<(Entity, &Coords, &mut Sprite), With<Square>>,
+    displayed_pieces: Query<(Entity, &Piece, &Coords, &Transform, Option<&PieceAnimation>)>,
+    transient_graphics: Query<Entity, Or<(With<Marker>, With<PromotionPopup>, With<PromotionChoice>)>>,
     turns: Res<Turns>,
     display_state: Res<BoardDisplayState>,
+    mut selections: ResMut<Selections>,
+    animation_settings: Res<AnimationSettings>,
+    mut displayed_board: Local<Option<Board>>,
     textures: Res<Textures>
 ) {
-    if !display_state.is_changed() { return; }
+    if !display_state.is_changed() && !turns.is_changed() { return; }
 
-    let Some(Turn { board, .. }) = turns.history.get(display_state.displayed_turn)
+    let dropped = selections.dropped;
+    // A held piece or a pending promotion belongs to the previously displayed turn
+    *selections = Selections::default();
+
+    let Some(current_turn @ Turn { board, previous_move, .. }) = turns.current()
     else { eprintln!("update_board_display: can't find board to display"); return };
 
-    let Ok(pg_entity) = set.p0().get_single()
+    let Ok(pg_entity) = playground.get_single()
     else { eprintln!("update_board_display: no spawned playground"); return };
 
-    for old_entity in set.p1().iter_mut() {
+    for old_entity in transient_graphics.iter() {
         if let Some(mut ec) = commands.get_entity(old_entity) {
             ec.despawn()
         }
     }
 
+    // Only a move from the board displayed until now is animated, jumps through the history are not
+    let previous_board = displayed_board.replace(board.clone());
+    let animated_move = turns
+        .parent(turns.current_id())
+        .and_then(|parent| turns.get(parent))
+        .filter(|parent_turn| animation_settings.speed > 0. && previous_board.as_ref() == Some(&parent_turn.board))
+        .map(|_| *previous_move);
+
+    let AnimationPlan { slides, captured: captured_coords, duration } = animated_move
+        .map(|move_| animation_plan(&move_, dropped, animation_settings.speed))
+        .unwrap_or_default();
+
+    let piece_rotation = Quat::from_rotation_z(
+        match display_state.bottom_side {
+            Side::White => 0.,
+            Side::Black => PI
+        }
+    );
+
+    let highlights = square_highlights(current_turn);
+
+    // Squares are added and removed when the board's shape changes, and only recolored otherwise
+    let mut old_squares: HashMap<Coords, Entity> = HashMap::new();
+    for (entity, &coords, mut sprite) in displayed_squares.iter_mut() {
+        let color = square_color(coords, highlights.get(&coords).copied());
+        if sprite.color != color {
+            sprite.color = color;
+        }
+        old_squares.insert(coords, entity);
+    }
+
     commands.entity(pg_entity).with_children(|parent| {
         for ((x, y), space) in board.spaces.indexed_iter() {
-            if *space != Space::Hole {
-                parent.spawn((
-                    Square,
-                    SpriteBundle {
-                        sprite: Sprite {
-                            color: if (x + y) % 2 == 0 {
-                                Color::rgb(0.2, 0.3, 0.4)
-                            } else {
-                                Color::rgb(0.8, 0.8, 0.8)
-                            },
-                            custom_size: Some(Vec2::ONE),
-                            ..default()
-                        },
-                        transform: Transform::from_translation(Vec3::new(
-                            x as f32,
-                            y as f32,
-                            SpriteHeight::Square as i32 as f32
-                        )),
-                        ..default()
-                    }
-                ));
-            }
-    
-            if let Space::Square { slot: Some(piece), .. } = space
-            {
-                parent.spawn((
-                    *piece,
-                    Coords {
-                        x: x as isize,
-                        y: y as isize,
-                    },
-                    SpriteSheetBundle {
-                        texture: textures.pieces.clone(),
-                        atlas: TextureAtlas { layout: textures.pieces_tal.clone(), index: piece.texture_index() },
-                        sprite: Sprite {
-                            custom_size: Some(Vec2::ONE),
-                            ..default()
-                        },
-                        transform: Transform::from_translation(Vec3::new(
-                            x as f32,
-                            y as f32,
-                            SpriteHeight::Piece as i32 as f32
-                        )).with_rotation(Quat::from_rotation_z(
-                            match display_state.bottom_side {
-                                Side::White => 0.,
-                                Side::Black => PI
-                            }
-                        )),
+            let coords = Coords {
+                x: x as isize,
+                y: y as isize,
+            };
+            if *space == Space::Hole || old_squares.remove(&coords).is_some() { continue; }
+
+            parent.spawn((
+                Square,
+                coords,
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: square_color(coords, highlights.get(&coords).copied()),
+                        custom_size: Some(Vec2::ONE),
                         ..default()
                     },
-                ));
-            }
+                    transform: Transform::from_translation(Vec3::new(
+                        x as f32,
+                        y as f32,
+                        SpriteHeight::Square as i32 as f32
+                    )),
+                    ..default()
+                }
+            ));
         }
     });
+    for entity in old_squares.into_values() {
+        commands.entity(entity).despawn();
+    }
+
+    // Pieces are matched with the displayed pieces looking the same on the square they come from
+    let mut old_pieces: HashMap<Coords, (Entity, Piece, Transform, bool)> = displayed_pieces
+        .iter()
+        .map(|(entity, &piece, &coords, &transform, animation)| (coords, (entity, piece, transform, animation.is_some())))
+        .collect();
+
+    for ((x, y), space) in board.spaces.indexed_iter() {
+        let Space::Square { slot: Some(piece), .. } = space else { continue };
+        let coords = Coords {
+            x: x as isize,
+            y: y as isize,
+        };
+        let slide = slides.iter().find(|(_, to)| *to == coords).map(|&(from, _)| from);
+        let start = slide.unwrap_or(coords);
+
+        let transform = |at: Coords, height: SpriteHeight| Transform::from_translation(Vec3::new(
+            at.x as f32,
+            at.y as f32,
+            height as i32 as f32
+        )).with_rotation(piece_rotation);
+        let animation = slide.map(|from| PieceAnimation {
+            from: Vec2::new(from.x as f32, from.y as f32),
+            to: Vec2::new(x as f32, y as f32),
+            elapsed: 0.,
+            duration,
+        });
+
+        let reused = old_pieces
+            .get(&start)
+            .filter(|(_, old_piece, ..)| old_piece.texture_index() == piece.texture_index())
+            .copied();
+        let mut piece_commands = if let Some((entity, old_piece, old_transform, animating)) = reused {
+            old_pieces.remove(&start);
+            let mut piece_commands = commands.entity(entity);
+
+            // Pieces are only touched when they moved, changed state, or were moved out of place by being held
+            if old_piece != *piece || start != coords {
+                piece_commands.insert((*piece, coords));
+            }
+            let target_transform = if slide.is_some() {
+                transform(start, SpriteHeight::HeldPiece)
+            } else if animating {
+                // A piece still sliding to its square keeps going
+                Transform { rotation: piece_rotation, ..old_transform }
+            } else {
+                transform(coords, SpriteHeight::Piece)
+            };
+            if target_transform != old_transform {
+                piece_commands.insert(target_transform);
+            }
+            piece_commands
+        } else {
+            let sprite = SpriteSheetBundle {
+                texture: textures.pieces.clone(),
+                atlas: TextureAtlas { layout: textures.pieces_tal.clone(), index: piece.texture_index() },
+                sprite: Sprite {
+                    custom_size: Some(Vec2::ONE),
+                    ..default()
+                },
+                transform: transform(start, if slide.is_some() { SpriteHeight::HeldPiece } else { SpriteHeight::Piece }),
+                ..default()
+            };
+            let entity = commands.spawn((*piece, coords, sprite)).id();
+            commands.entity(pg_entity).add_child(entity);
+            commands.entity(entity)
+        };
+
+        if let Some(animation) = animation {
+            piece_commands.insert(animation);
+        }
+    }
+
+    for (coords, (entity, ..)) in old_pieces {
+        // The captured piece fades out on the square it was taken on
+        if Some(coords) == captured_coords {
+            commands.entity(entity)
+                .remove::<(Piece, Coords, PieceAnimation)>()
+                .insert(FadingPiece { elapsed: 0., duration });
+        } else {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+// Slides moving pieces to their square and fades captured pieces out, with an ease-in-out curve
+fn animate_pieces(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut moving_pieces: Query<(Entity, &mut PieceAnimation, &mut Transform)>,
+    mut fading_pieces: Query<(Entity, &mut FadingPiece, &mut Sprite)>
+) {
+    let ease = |elapsed: f32, duration: f32| {
+        let t = (elapsed / duration).clamp(0., 1.);
+        t * t * (3. - 2. * t)
+    };
+
+    for (entity, mut animation, mut transform) in moving_pieces.iter_mut() {
+        animation.elapsed += time.delta_seconds();
+        let position = animation.from.lerp(animation.to, ease(animation.elapsed, animation.duration));
+
+        if animation.elapsed < animation.duration {
+            // Moving pieces pass over the others
+            transform.translation = position.extend(SpriteHeight::HeldPiece as i32 as f32);
+        } else {
+            transform.translation = position.extend(SpriteHeight::Piece as i32 as f32);
+            commands.entity(entity).remove::<PieceAnimation>();
+        }
+    }
+
+    for (entity, mut fading, mut sprite) in fading_pieces.iter_mut() {
+        fading.elapsed += time.delta_seconds();
+        sprite.color.set_a(1. - ease(fading.elapsed, fading.duration));
+
+        if fading.elapsed >= fading.duration {
+            commands.entity(entity).despawn();
+        }
+    }
 }
 
+// Pieces are moved either by dragging them to their target square,
+// or by clicking them then clicking their target square, with the mouse or on touch screens
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn move_piece(
     mut commands: Commands,
     mut turns: ResMut<Turns>,
-    mut display_state: ResMut<BoardDisplayState>,
+    display_state: Res<BoardDisplayState>,
     mut selections: ResMut<Selections>,
+    ai_players: Res<AIPlayers>,
     mut displayed_pieces: Query<(Entity, &Piece, &mut Transform, &Coords), Without<PromotionChoice>>,
     windows: Query<&Window>,
     buttons: Res<ButtonInput<MouseButton>>,
+    touches: Res<Touches>,
+    mut tracked_touch: Local<Option<u64>>,
     playground: Query<(Entity, &InverseGTransformCache), With<Playground>>,
     markers: Query<Entity, With<Marker>>,
     textures: Res<Textures>,
     promotion_choices: Query<(&PromotionChoice, &Transform)>,
     promotion_graphics: Query<Entity, Or<(With<PromotionPopup>, With<PromotionChoice>)>>
 ) {
-    let Some(displayed_turn @ Turn { board: displayed_board, .. }) = turns.history.get(display_state.displayed_turn)
+    let Some(displayed_turn @ Turn { board: displayed_board, .. }) = turns.current()
     else { eprintln!("select_piece: could not find current turn"); return };
 
     let Ok((pg_entity, InverseGTransformCache { matrix: pg_inv_matrix })) = playground.get_single() else { return };
     
-    let mouse_pos = {
-        let Ok(window) = windows.get_single() else { eprintln!("select_piece: Could not fetch window"); return };
-        let Some(mut pos) = window.cursor_position() else { return };
+    let Ok(window) = windows.get_single() else { eprintln!("select_piece: Could not fetch window"); return };
+    let Some(pointer) = PointerInput::read(window, &buttons, &touches, &mut tracked_touch) else { return };
+
+    let pointer_pos = {
+        let mut pos = pointer.position;
         pos.x -= window.width() / 2.;
         pos.y = window.height() / 2. - pos.y;
 
-        pg_inv_matrix.transform_point3(pos.extend(0.))
+        pg_inv_matrix.transform_point3(pos.extend(0.)).truncate()
     };
 
-    let mouse_coords = Coords {
-        x: mouse_pos.x.round() as isize,
-        y: mouse_pos.y.round() as isize,
-    };
+    // Pieces dragged with a finger are shown and dropped above it, towards the top of the screen
+    let screen_up = pg_inv_matrix.transform_vector3(Vec3::Y).truncate().normalize();
+    let drop_pos = Vec2::from(drop_position(pointer_pos.into(), screen_up.into(), pointer.touch));
 
+    let pointer_coords = square_at(pointer_pos.into());
+    let drop_coords = square_at(drop_pos.into());
 
     if let Some(ref mut prom_move) = selections.promotion {
-        if buttons.just_released(MouseButton::Left) {
+        // Choices are picked on press, so that the release ending a click on the promotion square doesn't pick one
+        if pointer.just_pressed {
             for (PromotionChoice(model), choice_transform) in promotion_choices.iter() {
-                if Vec2::distance(choice_transform.translation.truncate(),  mouse_pos.truncate()) < 0.5 {
+                if Vec2::distance(choice_transform.translation.truncate(), pointer_pos) < 0.5 {
                     prom_move.promotion = Some(*model);
 
-                    let new_board = get_next_board(displayed_board, prom_move);
-
-                    let new_turn = Turn {
-                        previous_move: *prom_move,
-                        possible_moves: compute_possible_moves(&new_board, true),
-                        board: new_board
-                    };
-                    
-                    turns.history.truncate(display_state.displayed_turn + 1);
-
-                    turns.history.push_back(new_turn);
-
-                    display_state.displayed_turn += 1;
+                    turns.push_move(prom_move);
                     
                     for entity in promotion_graphics.iter() {
                         commands.entity(entity).despawn();
@@ -277,219 +536,271 @@ fn move_piece(
             }
         }
     }
-    else if buttons.just_pressed(MouseButton::Left) {
-        // Get the movable piece at mouse position if it exists
-        if let Some((piece_entity, piece, mut piece_transform, piece_coords)) = displayed_pieces
-            .iter_mut()
-            .find(|(_, piece, _, &piece_coords)|
-                mouse_coords == piece_coords && piece.side == displayed_board.side
-            )
-        {
-            // Make it the currently selected piece
-            selections.piece = Some(piece_entity);
-
-            // Snap the piece to mouse position
-            piece_transform.translation = mouse_pos.truncate().extend(
-                SpriteHeight::HeldPiece as i32 as f32
-            );
-            piece_transform.scale = Vec2::splat(1.2).extend(1.);
-
-            // Display possible move markers
-            if let Some(moves) = displayed_turn.possible_moves.get(piece_coords) {
-                commands.entity(pg_entity).with_children(|parent| {
-                    for move_ in moves {
-                        parent.spawn((
-                            Marker,
-                            SpriteBundle {
-                                sprite: Sprite {
-                                    color: Color::rgb(0.2, 0.6, 0.3),
-                                    custom_size: Some(Vec2::ONE),
-                                    ..default()
-                                },
-                                transform: Transform::from_translation(Vec3::new(
-                                    move_.target.x as f32,
-                                    move_.target.y as f32,
-                                    SpriteHeight::MoveMarker as i32 as f32,
-                                )),
-                                texture: textures.marker.clone(),
-                                visibility: Visibility::Visible,
-                                ..default()
-                            }
-                        ));
-                    }
-                });
+    else if pointer.just_pressed {
+        let previous_selection = selections.selection.piece;
+        let previous_piece = previous_selection.and_then(|coords| piece_at(&displayed_pieces, coords));
+        // Pieces of computer-controlled sides cannot be picked up
+        let can_move = !ai_players.sides[displayed_board.side as usize];
+        let update = selections.selection.press(displayed_turn, pointer_coords, can_move);
+        deselect_piece(&mut commands, previous_selection, &mut displayed_pieces, &markers);
+
+        match update {
+            // Clicking a target square of the selected piece moves it there
+            SelectionUpdate::Deselected { moves } => {
+                if let (Some((piece_entity, piece)), false) = (previous_piece, moves.is_empty()) {
+                    selections.dropped = false;
+                    play_selected_moves(
+                        &mut commands, &mut turns, &mut selections, &display_state, &textures,
+                        pg_entity, piece_entity, piece, &moves
+                    );
+                }
             }
+            SelectionUpdate::PickedUp { coords } => {
+                let Some((piece_entity, piece)) = piece_at(&displayed_pieces, coords) else { return };
+
+                // A piece still sliding to its square is picked up where it is meant to be
+                commands.entity(piece_entity).remove::<PieceAnimation>();
+
+                // Snap the piece to pointer position, held until the button is released
+                if let Ok((_, _, mut piece_transform, _)) = displayed_pieces.get_mut(piece_entity) {
+                    piece_transform.translation = drop_pos.extend(
+                        SpriteHeight::HeldPiece as i32 as f32
+                    );
+                    piece_transform.scale = Vec2::splat(1.2).extend(1.);
+                }
 
-            // Display promotion squares markers
-            if let Piece { model: Pawn { .. }, side } = piece {
-                commands.entity(pg_entity).with_children(|parent| {
-                    for ((x, y), space) in displayed_turn.board.spaces.indexed_iter() {
-                        if let Space::Square { promotes, .. } = space {
-                            if promotes[*side as usize] {
-                                parent.spawn((
-                                    Marker,
-                                    SpriteBundle {
-                                        sprite: Sprite {
-                                            color: Color::rgb(0.8, 0.6, 0.),
-                                            custom_size: Some(Vec2::ONE),
-                                            ..default()
-                                        },
-                                        transform: Transform::from_translation(Vec3::new(
-                                            x as f32,
-                                            y as f32,
-                                            SpriteHeight::PromotionMarker as i32 as f32
-                                        )),
-                                        texture: textures.marker.clone(),
+                // Display possible move markers
+                if let Some(moves) = displayed_turn.possible_moves.get(&coords) {
+                    commands.entity(pg_entity).with_children(|parent| {
+                        for move_ in moves {
+                            parent.spawn((
+                                Marker,
+                                SpriteBundle {
+                                    sprite: Sprite {
+                                        color: Color::rgb(0.2, 0.6, 0.3),
+                                        custom_size: Some(Vec2::ONE),
                                         ..default()
                                     },
-                                ));
-                            }
+                                    transform: Transform::from_translation(Vec3::new(
+                                        move_.target.x as f32,
+                                        move_.target.y as f32,
+                                        SpriteHeight::MoveMarker as i32 as f32,
+                                    )),
+                                    texture: textures.marker.clone(),
+                                    visibility: Visibility::Visible,
+                                    ..default()
+                                }
+                            ));
                         }
-                    }
-                });
-            }
-        }
-    } else if let Some(piece_entity) = selections.piece {
-        // A piece is currently grabbed
-        if buttons.pressed(MouseButton::Left) {
-            if let Ok((_, _, mut piece_transform, _)) = displayed_pieces.get_mut(piece_entity) {
-                // Update its position to the mouse's
-                piece_transform.translation = mouse_pos.truncate().extend(
-                    SpriteHeight::HeldPiece as i32 as f32
-                );
-            }
-        }
-        // A piece is being released
-        else if buttons.just_released(MouseButton::Left) {
-            // Reset piece position
-            if let Ok((_, _, mut piece_transform, piece_coords)) = displayed_pieces.get_mut(piece_entity) {
-                piece_transform.translation = Vec3::new(
-                    piece_coords.x as f32,
-                    piece_coords.y as f32,
-                    SpriteHeight::Piece as i32 as f32
-                );
-                piece_transform.scale = Vec3::ONE;
-            }
-
-            // Reset selection
-            selections.piece = None;
-
-            // Stop displaying move markers
-            markers.iter().for_each(|marker_entity| {
-                if let Some(mut ec) = commands.get_entity(marker_entity) {
-                    ec.despawn()
+                    });
                 }
-            });
-
-            if let Ok((_, piece, _, piece_coords)) = displayed_pieces.get(piece_entity) {
-                if let Some(piece_moves) = displayed_turn.possible_moves.get(piece_coords) {
-                    // In the case of a promotion, there are multiple selected moves
-                    let selected_moves: Vec<_> = piece_moves
-                        .iter()
-                        .filter(|move_| {
-                            move_.target == mouse_coords
-                        })
-                        .collect();
-                    
-                    if !selected_moves.is_empty() {
-                        // Put up a popup for promotions
-                        if selected_moves.iter().all(|move_| move_.promotion.is_some()) {
-                            selections.promotion = Some(Move {
-                                promotion: None,
-                                ..**selected_moves.first().unwrap()
-                            });
-
-                            commands.entity(piece_entity).despawn();
-                            
-                            commands.entity(pg_entity).with_children(|parent| {
-                                let target = selected_moves.first().unwrap().target;
-
-                                parent.spawn((
-                                    PromotionPopup,
-                                    SpriteBundle {
-                                        sprite: Sprite {
-                                            color: Color::rgb(0.82, 0.63, 0.51),
-                                            custom_size: Some(Vec2 { x: 4.078, y: 1.078 }),
-                                            ..default()
-                                        },
-                                        transform: Transform::from_translation(Vec3::new(
-                                            target.x as f32,
-                                            target.y as f32,
-                                            SpriteHeight::PromotionPopup as i32 as f32
-                                        )),
-                                        texture: textures.promotion_popup.clone(),
-                                        ..default()
-                                    },
-                                ));
-                                
-                                let mut choice_x = target.x as f32 - (selected_moves.len() - 1) as f32 / 2.;
-
-                                for move_ in selected_moves {
-                                    let model = move_.promotion.unwrap();
 
+                // Display promotion squares markers
+                if let Piece { model: Pawn { .. }, side } = piece {
+                    commands.entity(pg_entity).with_children(|parent| {
+                        for ((x, y), space) in displayed_turn.board.spaces.indexed_iter() {
+                            if let Space::Square { promotes, .. } = space {
+                                if promotes[side as usize] {
                                     parent.spawn((
-                                        PromotionChoice(model),
-                                        SpriteSheetBundle {
-                                            texture: textures.pieces.clone(),
-                                            atlas: TextureAtlas {
-                                                layout: textures.pieces_tal.clone(),
-                                                index: Piece {
-                                                    model,
-                                                    side: piece.side
-                                                }.texture_index()
-                                            },
+                                        Marker,
+                                        SpriteBundle {
                                             sprite: Sprite {
+                                                color: Color::rgb(0.8, 0.6, 0.),
                                                 custom_size: Some(Vec2::ONE),
                                                 ..default()
                                             },
                                             transform: Transform::from_translation(Vec3::new(
-                                                choice_x,
-                                                target.y as f32,
-                                                SpriteHeight::PromotionPopupPiece as i32 as f32
-                                            )).with_rotation(Quat::from_rotation_z(
-                                                match display_state.bottom_side {
-                                                    Side::White => 0.,
-                                                    Side::Black => PI
-                                                }
+                                                x as f32,
+                                                y as f32,
+                                                SpriteHeight::PromotionMarker as i32 as f32
                                             )),
+                                            texture: textures.marker.clone(),
                                             ..default()
                                         },
                                     ));
-
-                                    choice_x += 1.0;
                                 }
-
-                            });
+                            }
                         }
-                        // Add a turn to the turn history if a valid move has been played
-                        // All graphical updates will be handled later by update_board_display
-                        else if selected_moves.len() == 1 {
-                            let selected_move = selected_moves.first().unwrap();
-
-                            let new_board = get_next_board(displayed_board, selected_move);
-
-                            let new_turn = Turn {
-                                previous_move: **selected_move,
-                                possible_moves: compute_possible_moves(&new_board, true),
-                                board: new_board
-                            };
-                            
-                            turns.history.truncate(display_state.displayed_turn + 1);
-
-                            turns.history.push_back(new_turn);
+                    });
+                }
+            }
+            SelectionUpdate::PutDown => ()
+        }
+    } else if let Some(piece_coords) = selections.selection.piece.filter(|_| selections.selection.held) {
+        let Some((piece_entity, piece)) = piece_at(&displayed_pieces, piece_coords) else { return };
 
-                            display_state.displayed_turn += 1;
-                        }
-                        else {
-                            eprintln!("move_piece: mix of promotion and non-promotion moves");
-                        }
+        // A piece is currently grabbed
+        if pointer.pressed {
+            if let Ok((_, _, mut piece_transform, _)) = displayed_pieces.get_mut(piece_entity) {
+                // Update its position to the pointer's
+                piece_transform.translation = drop_pos.extend(
+                    SpriteHeight::HeldPiece as i32 as f32
+                );
+            }
+        }
+        // A piece is being released
+        else if pointer.just_released {
+            match selections.selection.release(displayed_turn, pointer_coords, drop_coords) {
+                Some(SelectionUpdate::PutDown) => {
+                    if let Ok((_, _, mut piece_transform, _)) = displayed_pieces.get_mut(piece_entity) {
+                        piece_transform.translation = Vec3::new(
+                            piece_coords.x as f32,
+                            piece_coords.y as f32,
+                            SpriteHeight::Piece as i32 as f32
+                        );
+                    }
+                }
+                Some(SelectionUpdate::Deselected { moves }) => {
+                    deselect_piece(&mut commands, Some(piece_coords), &mut displayed_pieces, &markers);
+
+                    if !moves.is_empty() {
+                        selections.dropped = true;
+                        play_selected_moves(
+                            &mut commands, &mut turns, &mut selections, &display_state, &textures,
+                            pg_entity, piece_entity, piece, &moves
+                        );
                     }
                 }
+                _ => ()
             }
         }
     }
 }
 
+// Displayed piece standing on a square, if any
+fn piece_at(
+    displayed_pieces: &Query<(Entity, &Piece, &mut Transform, &Coords), Without<PromotionChoice>>,
+    coords: Coords
+) -> Option<(Entity, Piece)> {
+    displayed_pieces
+        .iter()
+        .find(|(_, _, _, &piece_coords)| piece_coords == coords)
+        .map(|(entity, &piece, ..)| (entity, piece))
+}
+
+// Puts the piece that was selected back on its square and stops displaying its move markers
+fn deselect_piece(
+    commands: &mut Commands,
+    selected_piece: Option<Coords>,
+    displayed_pieces: &mut Query<(Entity, &Piece, &mut Transform, &Coords), Without<PromotionChoice>>,
+    markers: &Query<Entity, With<Marker>>
+) {
+    let Some(piece_coords) = selected_piece else { return };
+
+    // Reset piece position
+    if let Some((_, _, mut piece_transform, _)) = displayed_pieces
+        .iter_mut()
+        .find(|(_, _, _, &coords)| coords == piece_coords)
+    {
+        piece_transform.translation = Vec3::new(
+            piece_coords.x as f32,
+            piece_coords.y as f32,
+            SpriteHeight::Piece as i32 as f32
+        );
+        piece_transform.scale = Vec3::ONE;
+    }
+
+    // Stop displaying move markers
+    markers.iter().for_each(|marker_entity| {
+        if let Some(mut ec) = commands.get_entity(marker_entity) {
+            ec.despawn()
+        }
+    });
+}
+
+// Plays the move of a piece to the square it was dropped on or clicked to,
+// or puts up a popup to pick a piece when the move is a promotion
+#[allow(clippy::too_many_arguments)]
+fn play_selected_moves(
+    commands: &mut Commands,
+    turns: &mut Turns,
+    selections: &mut Selections,
+    display_state: &BoardDisplayState,
+    textures: &Textures,
+    pg_entity: Entity,
+    piece_entity: Entity,
+    piece: Piece,
+    selected_moves: &[Move]
+) {
+    // Put up a popup for promotions
+    if selected_moves.iter().all(|move_| move_.promotion.is_some()) {
+        selections.promotion = Some(Move {
+            promotion: None,
+            ..selected_moves[0]
+        });
+
+        commands.entity(piece_entity).despawn();
+        
+        commands.entity(pg_entity).with_children(|parent| {
+            let target = selected_moves[0].target;
+
+            parent.spawn((
+                PromotionPopup,
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::rgb(0.82, 0.63, 0.51),
+                        custom_size: Some(Vec2 { x: 4.078, y: 1.078 }),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(Vec3::new(
+                        target.x as f32,
+                        target.y as f32,
+                        SpriteHeight::PromotionPopup as i32 as f32
+                    )),
+                    texture: textures.promotion_popup.clone(),
+                    ..default()
+                },
+            ));
+            
+            let mut choice_x = target.x as f32 - (selected_moves.len() - 1) as f32 / 2.;
+
+            for move_ in selected_moves {
+                let model = move_.promotion.unwrap();
+
+                parent.spawn((
+                    PromotionChoice(model),
+                    SpriteSheetBundle {
+                        texture: textures.pieces.clone(),
+                        atlas: TextureAtlas {
+                            layout: textures.pieces_tal.clone(),
+                            index: Piece {
+                                model,
+                                side: piece.side
+                            }.texture_index()
+                        },
+                        sprite: Sprite {
+                            custom_size: Some(Vec2::ONE),
+                            ..default()
+                        },
+                        transform: Transform::from_translation(Vec3::new(
+                            choice_x,
+                            target.y as f32,
+                            SpriteHeight::PromotionPopupPiece as i32 as f32
+                        )).with_rotation(Quat::from_rotation_z(
+                            match display_state.bottom_side {
+                                Side::White => 0.,
+                                Side::Black => PI
+                            }
+                        )),
+                        ..default()
+                    },
+                ));
+
+                choice_x += 1.0;
+            }
+
+        });
+    }
+    // Add a turn to the game tree if a valid move has been played
+    // All graphical updates will be handled later by update_board_display
+    else if selected_moves.len() == 1 {
+        turns.push_move(&selected_moves[0]);
+    }
+    else {
+        eprintln!("move_piece: mix of promotion and non-promotion moves");
+    }
+}
+
 // This system should be run in PostUpdate after transform propagation
 fn update_transform_cache(
     mut query: Query<(&mut InverseGTransformCache, &GlobalTransform), Changed<GlobalTransform>>,
@@ -503,6 +814,7 @@ fn update_transform_cache(
 // - piece positions reflect their coordinates on the board
 //   (with the origin set to the bottom-left square seen from the white side)
 // - pieces have a sprite size of (1.,1.)
+#[allow(clippy::type_complexity)]
 fn update_playground_transform(
     mut resize_events: EventReader<WindowResized>,
     mut set: ParamSet<(
@@ -513,8 +825,8 @@ fn update_playground_transform(
     display_state: Res<BoardDisplayState>,
     windows: Query<&Window>
 ) {
-    let Some(Turn { board, .. }) = turns.history.get(display_state.displayed_turn)
-    else { eprintln!("update_playground_transform: no board in history"); return };
+    let Some(Turn { board, .. }) = turns.current()
+    else { eprintln!("update_playground_transform: no current turn"); return };
 
     let (bw, bh) = board.spaces.dim();
     let (bw, bh) = (bw as f32, bh as f32);
@@ -556,10 +868,10 @@ fn update_playground_transform(
     };
 
     for event in resize_events.read() {
-        update_transforms(event.width as f32, event.height as f32);
+        update_transforms(event.width, event.height);
     }
 
-    if display_state.is_changed() {
+    if display_state.is_changed() || turns.is_changed() {
         let Ok(window) = windows.get_single()
         else { eprintln!("update_playground_transform: could not fetch window"); return };
 
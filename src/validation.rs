@@ -0,0 +1,186 @@
+use std::fmt;
+
+use crate::board::PieceModel::*;
+use crate::board::Space::*;
+use crate::board::*;
+use crate::logic::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+// Problem found in a starting layout
+// Errors make the layout unplayable, warnings point at likely mistakes
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LayoutIssue {
+    NoKing { side: Side },
+    InCheck { side: Side },
+    OpponentInCheck { side: Side },
+    AlreadyOver { outcome: GameOutcome },
+    PawnOnPromotionSquare { coords: Coords, side: Side },
+    PawnCannotPromote { coords: Coords, side: Side },
+    DisconnectedRegions { count: usize },
+}
+
+impl LayoutIssue {
+    pub fn severity(&self) -> Severity {
+        match self {
+            LayoutIssue::OpponentInCheck { .. }
+            | LayoutIssue::AlreadyOver { .. }
+            | LayoutIssue::PawnOnPromotionSquare { .. } => Severity::Error,
+            LayoutIssue::NoKing { .. }
+            | LayoutIssue::InCheck { .. }
+            | LayoutIssue::PawnCannotPromote { .. }
+            | LayoutIssue::DisconnectedRegions { .. } => Severity::Warning,
+        }
+    }
+}
+
+// Wrapper displaying coordinates the way layout strings are written, rows being counted from the top
+struct LayoutPosition<'a>(&'a Board, Coords);
+
+impl fmt::Display for LayoutPosition<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let LayoutPosition(board, coords) = self;
+        let (_, bh) = board.spaces.dim();
+        write!(f, "row {}, column {}", bh as isize - coords.y, coords.x + 1)
+    }
+}
+
+impl LayoutIssue {
+    pub fn describe(&self, board: &Board) -> String {
+        let severity = match self.severity() {
+            Severity::Warning => "Warning",
+            Severity::Error => "Error",
+        };
+
+        let description = match self {
+            LayoutIssue::NoKing { side } => format!("{:?} has no king and can never be checkmated", side),
+            LayoutIssue::InCheck { side } => format!("{:?} starts in check", side),
+            LayoutIssue::OpponentInCheck { side } => {
+                format!("{:?} is in check while {:?} is to move", side, side.other())
+            }
+            LayoutIssue::AlreadyOver { outcome } => format!("the game is already over: {:?}", outcome),
+            LayoutIssue::PawnOnPromotionSquare { coords, side } => format!(
+                "{:?} pawn at {} starts on one of its promotion squares",
+                side,
+                LayoutPosition(board, *coords)
+            ),
+            LayoutIssue::PawnCannotPromote { coords, side } => format!(
+                "{:?} pawn at {} can never reach a promotion square",
+                side,
+                LayoutPosition(board, *coords)
+            ),
+            LayoutIssue::DisconnectedRegions { count } => {
+                format!("the board is split into {} disconnected regions", count)
+            }
+        };
+
+        format!("{}: {}", severity, description)
+    }
+}
+
+// Checks a starting layout for unplayable or suspicious setups
+pub fn validate_board(board: &Board) -> Vec<LayoutIssue> {
+    let mut issues = vec![];
+
+    for side in [Side::White, Side::Black] {
+        let has_king = board.spaces.iter().any(|space| {
+            matches!(space, Square { slot: Some(Piece { model: King { .. }, side: king_side }), .. } if *king_side == side)
+        });
+        if !has_king {
+            issues.push(LayoutIssue::NoKing { side });
+        }
+    }
+
+    let mut opponent_board = Board {
+        side: board.side.other(),
+        ..board.clone()
+    };
+    opponent_board.hash = opponent_board.compute_hash();
+    if is_in_check(&opponent_board) {
+        issues.push(LayoutIssue::OpponentInCheck { side: board.side.other() });
+    } else {
+        // Legal moves are only meaningful if the side to move can't take a king
+        let outcome = compute_outcome(board, &compute_possible_moves(board, true));
+        if outcome != GameOutcome::Ongoing {
+            issues.push(LayoutIssue::AlreadyOver { outcome });
+        } else if is_in_check(board) {
+            issues.push(LayoutIssue::InCheck { side: board.side });
+        }
+    }
+
+    for ((x, y), space) in board.spaces.indexed_iter() {
+        if let Square { slot: Some(Piece { model: Pawn { .. }, side }), promotes } = space {
+            let coords = Coords { x: x as isize, y: y as isize };
+            if promotes[*side as usize] {
+                issues.push(LayoutIssue::PawnOnPromotionSquare { coords, side: *side });
+            } else if !can_reach_promotion(board, coords, *side) {
+                issues.push(LayoutIssue::PawnCannotPromote { coords, side: *side });
+            }
+        }
+    }
+
+    let regions = count_regions(board);
+    if regions > 1 {
+        issues.push(LayoutIssue::DisconnectedRegions { count: regions });
+    }
+
+    issues
+}
+
+// Explores every square a pawn could ever step or capture onto, ignoring the other pieces
+fn can_reach_promotion(board: &Board, start: Coords, side: Side) -> bool {
+    let y_dir = if side == Side::White { 1 } else { -1 };
+    let mut visited = vec![start];
+    let mut frontier = vec![start];
+
+    while let Some(Coords { x, y }) = frontier.pop() {
+        for target in [-1, 0, 1].map(|dx| Coords { x: x + dx, y: y + y_dir }) {
+            if visited.contains(&target) {
+                continue;
+            }
+            if let Some(Square { promotes, .. }) = board.spaces.get(target) {
+                if promotes[side as usize] {
+                    return true;
+                }
+                visited.push(target);
+                frontier.push(target);
+            }
+        }
+    }
+
+    false
+}
+
+// Number of groups of squares connected through king steps
+fn count_regions(board: &Board) -> usize {
+    let mut region_ids = board.spaces.map(|_| None::<usize>);
+    let mut regions = 0;
+
+    for ((x, y), space) in board.spaces.indexed_iter() {
+        if !matches!(space, Square { .. }) || region_ids[(x, y)].is_some() {
+            continue;
+        }
+
+        let mut frontier = vec![Coords { x: x as isize, y: y as isize }];
+        region_ids[(x, y)] = Some(regions);
+        while let Some(coords) = frontier.pop() {
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    let neighbour = Coords { x: coords.x + dx, y: coords.y + dy };
+                    if matches!(board.spaces.get(neighbour), Some(Square { .. })) && region_ids[neighbour].is_none() {
+                        region_ids[neighbour] = Some(regions);
+                        frontier.push(neighbour);
+                    }
+                }
+            }
+        }
+
+        regions += 1;
+    }
+
+    regions
+}
@@ -0,0 +1,184 @@
+// Engine: best moves found by the search, positions without moves, unusual boards and depth limits
+
+use chess2::ai::*;
+use chess2::board::PieceModel::*;
+use chess2::board::*;
+use chess2::logic::*;
+
+const STANDARD_PROMOTIONS: &str = "WWWWWWWW/8/8/8/8/8/8/bbbbbbbb";
+
+fn board(squares: &str, side: char) -> Board {
+    Board::from_notation(&format!("{} {} {} -", squares, STANDARD_PROMOTIONS, side)).unwrap()
+}
+
+fn coords(x: isize, y: isize) -> Coords {
+    Coords { x, y }
+}
+
+fn limits(max_depth: u32) -> SearchLimits {
+    SearchLimits { max_depth, ..SearchLimits::default() }
+}
+
+fn is_legal(board: &Board, move_: &Move) -> bool {
+    compute_possible_moves(board, true).values().flatten().any(|legal| legal == move_)
+}
+
+#[test]
+fn mate_in_one() {
+    let board = board("k7/8/1K6/8/8/8/8/6Q1", 'w');
+    let result = search(&board, limits(4)).unwrap();
+
+    // Found at the first depth reaching it, mates being searched no deeper
+    assert_eq!(result.score, MATE_SCORE - 1);
+    assert_eq!(result.depth, 2);
+    let next_board = get_next_board(&board, &result.best_move);
+    let outcome = compute_outcome(&next_board, &compute_possible_moves(&next_board, true));
+    assert_eq!(outcome, GameOutcome::Checkmate { winner: Side::White });
+}
+
+#[test]
+fn hanging_queen() {
+    let board = board("k7/8/8/3q4/8/8/8/K2R4", 'w');
+    let result = search(&board, limits(3)).unwrap();
+    assert_eq!((result.best_move.source, result.best_move.target), (coords(3, 0), coords(3, 4)));
+    assert_eq!(result.best_move.kind, MoveKind::Capture);
+    assert!(result.score > piece_value(Queen) - piece_value(Rook { can_castle: false }));
+}
+
+#[test]
+fn no_legal_moves() {
+    // Stalemate and checkmate
+    assert_eq!(search(&board("k7/8/1Q6/8/8/8/8/K7", 'b'), limits(3)), None);
+    assert_eq!(search(&board("k7/1Q6/1K6/8/8/8/8/8", 'b'), limits(3)), None);
+    assert!(Searcher::new(&board("k7/1Q6/1K6/8/8/8/8/8", 'b'), limits(3)).is_none());
+}
+
+#[test]
+fn holes_and_several_kings() {
+    // Two kings on each side, and holes in the middle of the board
+    let attacked = board("k6k/8/2XXXX2/2XXXX2/8/1q6/8/KR5K", 'w');
+    let result = search(&attacked, limits(3)).unwrap();
+    assert!(is_legal(&attacked, &result.best_move));
+    assert_eq!((result.best_move.source, result.best_move.target), (coords(1, 0), coords(1, 2)));
+
+    // Only one of the kings is in check, and no move may leave it there
+    let checked = board("k6k/8/XXXXXX2/8/8/8/8/K6R", 'b');
+    let result = search(&checked, limits(3)).unwrap();
+    assert!(is_legal(&checked, &result.best_move));
+    assert_eq!(result.best_move.source, coords(7, 7));
+    assert!(!is_in_check_after_move(&checked, &result.best_move));
+}
+
+#[test]
+fn depth_limits() {
+    let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+    for max_depth in 1..=3 {
+        let result = search(&board, limits(max_depth)).unwrap();
+        assert_eq!(result.depth, max_depth);
+        assert!(is_legal(&board, &result.best_move));
+    }
+
+    // Without any depth, the first move in search order is returned without searching
+    let result = search(&board, limits(0)).unwrap();
+    assert_eq!((result.depth, result.nodes), (0, 0));
+    assert!(is_legal(&board, &result.best_move));
+}
+
+// Negamax with alpha-beta pruning only, without table nor move ordering, scoring positions the way the engine does
+fn reference_negamax(board: &Board, depth: u32, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+    if depth == 0 {
+        return evaluate(board);
+    }
+    let moves: Vec<Move> = compute_possible_moves(board, true).into_values().flatten().collect();
+    if moves.is_empty() {
+        return if is_in_check(board) { -MATE_SCORE + ply } else { 0 };
+    }
+    for move_ in &moves {
+        let score = -reference_negamax(&get_next_board(board, move_), depth - 1, ply + 1, -beta, -alpha);
+        if score >= beta {
+            return beta;
+        }
+        alpha = alpha.max(score);
+    }
+    alpha
+}
+
+fn reference_score(board: &Board, depth: u32) -> i32 {
+    reference_negamax(board, depth, 0, -MATE_SCORE - 1, MATE_SCORE + 1)
+}
+
+#[test]
+fn same_scores_as_reference_search() {
+    // Whatever the table, killer moves and history make the engine search first, it finds the same scores
+    for (squares, side, max_depth) in [
+        ("k7/8/1K6/8/8/8/8/6Q1", 'w', 4),
+        ("7k/8/5K2/8/8/8/8/R7", 'w', 5),
+        ("8/8/8/3k4/8/8/8/K2Q4", 'w', 5),
+        ("r5k1/5ppp/8/8/8/8/5PPP/R5K1", 'w', 5),
+        ("4k3/8/8/3q4/8/8/3R4/4K3", 'b', 5),
+        ("k7/2K5/8/8/8/8/8/1R6", 'b', 6),
+    ] {
+        let board = board(squares, side);
+        let result = search(&board, limits(max_depth)).unwrap();
+        assert_eq!(result.score, reference_score(&board, result.depth), "{}", squares);
+    }
+}
+
+#[test]
+fn mate_distances_through_the_table() {
+    // A position searched early with a deep draft is found again further from the root, where its stored mate is
+    // used without searching it again, showing a mate one move beyond the search depth
+    let board = board("2R5/8/8/8/6Q1/8/p2k4/K7", 'w');
+    let result = search(&board, limits(5)).unwrap();
+    assert_eq!(result.depth, 5);
+    assert!(reference_score(&board, 5) < MATE_SCORE - 1000);
+    // Its distance is counted from the root rather than from where it was stored
+    assert_eq!(result.score, MATE_SCORE - 5);
+    assert_eq!(result.score, reference_score(&board, 6));
+}
+
+#[test]
+fn capture_order() {
+    // Before searching, the best move is the first in search order: the most valuable victim, by the least valuable attacker
+    let board = board("k7/8/8/2q1r3/1P6/3N4/8/K7", 'w');
+    let searcher = Searcher::new(&board, limits(3)).unwrap();
+    let first_move = searcher.result().best_move;
+    assert_eq!((first_move.source, first_move.target), (coords(1, 3), coords(2, 4)));
+}
+
+#[test]
+fn node_limits() {
+    let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+    let max_nodes = 5000;
+    let result = search(&board, SearchLimits { max_nodes, ..SearchLimits::default() }).unwrap();
+    // The search stops at the first node past the limit, before reaching its depth, with a legal move
+    assert_eq!(result.nodes, max_nodes + 1);
+    assert!(result.depth < SearchLimits::default().max_depth);
+    assert!(is_legal(&board, &result.best_move));
+
+    // Steps go past their budget by at most the rest of a root move's subtree, and stop at the node limit too
+    let mut searcher = Searcher::new(&board, SearchLimits { max_nodes, ..SearchLimits::default() }).unwrap();
+    let mut steps = 0;
+    while !searcher.step(1000) {
+        steps += 1;
+        assert!(searcher.result().nodes >= steps * 1000);
+    }
+    assert_eq!(searcher.result().nodes, max_nodes + 1);
+    assert!(steps < 5);
+    assert!(is_legal(&board, &searcher.result().best_move));
+}
+
+#[test]
+fn table_sizes() {
+    let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+    // Sizes are rounded down to a power of two, and the table never grows past them however many positions are searched
+    for (table_size, entries) in [(0, 1), (1, 1), (100, 64), (1000, 512)] {
+        let limits = SearchLimits { max_depth: 4, table_size, ..SearchLimits::default() };
+        let mut searcher = Searcher::new(&board, limits).unwrap();
+        searcher.step(u64::MAX);
+        // Enough positions were searched to fill most slots
+        assert!(searcher.result().nodes > 4 * entries as u64);
+        assert!(searcher.table_entries() <= entries);
+        assert!(searcher.table_entries() > entries / 2);
+    }
+}
@@ -0,0 +1,115 @@
+// Board formats: the layout strings and the single-line position notation
+
+use chess2::board::*;
+
+#[test]
+fn notation_round_trip() {
+    // Every field: castling and dash markers, holes, both promotion sides, Black to move and captured pieces
+    let notation = "r'3k2r/p1pX1p'2/4P'p!2/8/8/2X5/P4PPP/R3K'2R WW*WWWWW/8/8/8/8/8/8/bbbbbbbb b QRp'r'";
+    let board = Board::from_notation(notation).unwrap();
+    assert_eq!(board.to_notation(), notation);
+    assert_eq!(board.side, Side::Black);
+    assert_eq!(board.captured.len(), 4);
+
+    // The spaces hold what the notation describes
+    let space = |x, y| board.spaces[(x, y)];
+    assert_eq!(space(3, 6), Space::Hole);
+    assert!(matches!(space(0, 7), Space::Square { slot: Some(Piece { model: PieceModel::Rook { can_castle: false }, .. }), .. }));
+    assert!(matches!(space(7, 7), Space::Square { slot: Some(Piece { model: PieceModel::Rook { can_castle: true }, .. }), .. }));
+    assert!(matches!(
+        space(5, 5),
+        Space::Square { slot: Some(Piece { model: PieceModel::Pawn { can_dash: true, just_dashed: true }, side: Side::Black }), .. }
+    ));
+    assert!(matches!(space(2, 7), Space::Square { promotes: [true, true], .. }));
+    assert!(matches!(space(0, 0), Space::Square { promotes: [false, true], .. }));
+    assert_eq!(Board::from_notation(&board.to_notation()).unwrap(), board);
+}
+
+#[test]
+fn invalid_notation() {
+    for notation in [
+        "k7/8/8/8/8/8/8/K7 8/8/8/8/8/8/8/8 w",
+        // Runs longer than the row, including ones too large to allocate
+        "k7/9/8/8/8/8/8/K7 8/8/8/8/8/8/8/8 w -",
+        "k99999999999/8/8/8/8/8/8/K7 8/8/8/8/8/8/8/8 w -",
+        "k7/8/8/8/8/8/8/K7 99999999999/8/8/8/8/8/8/8 w -",
+        // Repeated or misplaced markers
+        "k''7/8/8/8/8/8/8/K7 8/8/8/8/8/8/8/8 w -",
+        "k7/p!!7/8/8/8/8/8/K7 8/8/8/8/8/8/8/8 w -",
+        "k!7/8/8/8/8/8/8/K7 8/8/8/8/8/8/8/8 w -",
+        "k7/8/8/8/8/8/8/K7 8/8/8/8/8/8/8/8 w N'",
+        "k7/8/8/8/8/8/8/K7 8/8/8/8/8/8/8/8 w Q''",
+        // Promotions on holes, unknown side
+        "kX6/8/8/8/8/8/8/K7 1W6/8/8/8/8/8/8/8 w -",
+        "k7/8/8/8/8/8/8/K7 8/8/8/8/8/8/8/8 x -",
+    ] {
+        assert!(Board::from_notation(notation).is_err(), "{:?}", notation);
+    }
+
+    // Too many rows, which would be as large as too long runs, in the squares or in the promotions
+    let rows = "1024/".repeat(1 << 20);
+    let tall_squares = format!("{}1024 {}1024 w -", rows, rows);
+    assert_eq!(Board::from_notation(&tall_squares), Err("Too many rows in position notation"));
+    let tall_promotions = format!("k7/8/8/8/8/8/8/K7 {}8 w -", "8/".repeat(1 << 20));
+    assert_eq!(Board::from_notation(&tall_promotions), Err("Too many rows in position notation"));
+
+    // The tallest board accepted
+    let rows = "/1".repeat(1023);
+    let tallest = format!("k{} 1{} w -", rows, rows);
+    assert_eq!(Board::from_notation(&tallest).unwrap().spaces.dim(), (1, 1024));
+}
+
+#[test]
+fn layout_strings_round_trip() {
+    let pieces = "rnbqkbnr\npppXXppp\n________\n________\n________\n________\nPPPXXPPP\nRNBQKBNR";
+    let promotions = "WW*WWWWW\n___XX___\n________\n________\n________\n________\n___XX___\nbbbbbbbb";
+    let board = Board::from_strings_strict(pieces, promotions).unwrap();
+    assert_eq!(board.to_strings(), (String::from(pieces), String::from(promotions)));
+    assert_eq!(board.spaces[(3, 6)], Space::Hole);
+
+    // Alternative promotion symbols, X under squares and surrounding whitespace are written back in their usual form
+    let loose = Board::from_strings("  k_\n\n  _K\n", "PX\np_").unwrap();
+    assert_eq!(loose.to_strings(), (String::from("k_\n_K"), String::from("W_\nb_")));
+    let (pieces, promotions) = loose.to_strings();
+    assert_eq!(Board::from_strings_strict(&pieces, &promotions).unwrap(), loose);
+}
+
+#[test]
+fn layout_errors() {
+    assert_eq!(Board::from_strings("\n  \n", "").unwrap_err(), LayoutError::Empty);
+    assert_eq!(
+        Board::from_strings("k_\n_K", "__").unwrap_err(),
+        LayoutError::RowCountMismatch { pieces_rows: 2, promotions_rows: 1 }
+    );
+
+    // Rows are counted from the top of each string, blank lines included
+    assert_eq!(
+        Board::from_strings("k_\n\n_K_", "__\n__").unwrap_err(),
+        LayoutError::RowWidthMismatch { string: LayoutString::Pieces, row: 3, expected: 2, found: 3 }
+    );
+    assert_eq!(
+        Board::from_strings("k_\n_K", "__\n_").unwrap_err(),
+        LayoutError::RowWidthMismatch { string: LayoutString::Promotions, row: 2, expected: 2, found: 1 }
+    );
+
+    // Columns include the indentation of the row
+    assert_eq!(
+        Board::from_strings("k_\n  _é", "__\n__").unwrap_err(),
+        LayoutError::InvalidSymbol { string: LayoutString::Pieces, row: 2, column: 4, symbol: 'é' }
+    );
+}
+
+#[test]
+fn strict_layouts() {
+    // Unknown ASCII symbols are read as empty squares without promotions, unless parsing strictly
+    let loose = Board::from_strings("k?\n_K", "_?\n__").unwrap();
+    assert_eq!(loose.spaces[(1, 1)], Space::Square { slot: None, promotes: [false, false] });
+    assert_eq!(
+        Board::from_strings_strict("k?\n_K", "__\n__").unwrap_err(),
+        LayoutError::InvalidSymbol { string: LayoutString::Pieces, row: 1, column: 2, symbol: '?' }
+    );
+    assert_eq!(
+        Board::from_strings_strict("k_\n_K", "__\n_?").unwrap_err(),
+        LayoutError::InvalidSymbol { string: LayoutString::Promotions, row: 2, column: 2, symbol: '?' }
+    );
+}
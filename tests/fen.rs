@@ -0,0 +1,136 @@
+// FEN: import of standard positions and export of the boards that fit the standard rules
+
+use chess2::board::PieceModel::*;
+use chess2::board::*;
+use chess2::logic::*;
+use chess2::notation::*;
+use chess2::turns::*;
+
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+fn piece(board: &Board, name: &str) -> Piece {
+    match board.spaces.get(parse_square(name).unwrap()) {
+        Some(Space::Square { slot: Some(piece), .. }) => *piece,
+        _ => panic!("no piece on {}", name),
+    }
+}
+
+fn play(board: &Board, sans: &[&str]) -> Board {
+    sans.iter().fold(board.clone(), |board, san| get_next_board(&board, &parse_san(&board, san).unwrap()))
+}
+
+#[test]
+fn start_position() {
+    let board = Board::from_fen(START_FEN).unwrap();
+    let standard = Board::from_strings_strict(
+        "rnbqkbnr\npppppppp\n________\n________\n________\n________\nPPPPPPPP\nRNBQKBNR",
+        "WWWWWWWW\n________\n________\n________\n________\n________\n________\nbbbbbbbb",
+    )
+    .unwrap();
+    assert!(board == standard);
+    assert_eq!(board.to_fen(0, 1).unwrap(), START_FEN);
+
+    // Clocks may be left out
+    let (_, halfmove_clock, fullmove_number) =
+        Board::from_fen_with_clocks("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -").unwrap();
+    assert_eq!((halfmove_clock, fullmove_number), (0, 1));
+
+    let after_e4 = play(&board, &["e4"]);
+    assert_eq!(after_e4.to_fen(0, 1).unwrap(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+
+    // Moving a king or a rook gives up the castling rights it held
+    let after_rook_moves = play(&board, &["Nf3", "Nc6", "Rg1", "Rb8"]);
+    assert_eq!(after_rook_moves.to_fen(4, 3).unwrap(), "1rbqkbnr/pppppppp/2n5/8/8/5N2/PPPPPPPP/RNBQKBR1 w Qk - 4 3");
+}
+
+#[test]
+fn castling_and_en_passant() {
+    let fen = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w Kq f6 0 3";
+    let (board, halfmove_clock, fullmove_number) = Board::from_fen_with_clocks(fen).unwrap();
+    assert_eq!((halfmove_clock, fullmove_number), (0, 3));
+
+    assert_eq!(piece(&board, "e1").model, King { can_castle: true });
+    assert_eq!(piece(&board, "h1").model, Rook { can_castle: true });
+    assert_eq!(piece(&board, "a1").model, Rook { can_castle: false });
+    assert_eq!(piece(&board, "e8").model, King { can_castle: true });
+    assert_eq!(piece(&board, "a8").model, Rook { can_castle: true });
+    assert_eq!(piece(&board, "h8").model, Rook { can_castle: false });
+
+    assert_eq!(piece(&board, "f5").model, Pawn { can_dash: false, just_dashed: true });
+    assert_eq!(piece(&board, "d5").model, Pawn { can_dash: false, just_dashed: false });
+    let capture = parse_san(&board, "exf6").unwrap();
+    assert_eq!(capture.kind, MoveKind::EnPassant);
+    assert!(parse_san(&board, "exd6").is_err());
+
+    assert_eq!(board.to_fen(halfmove_clock, fullmove_number).unwrap(), fen);
+}
+
+#[test]
+fn continued_games() {
+    // A position imported from elsewhere keeps its clocks, which go on from there as moves are played
+    let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 5 30";
+    let initial_turn = Turn::from_fen(fen).unwrap();
+    assert_eq!((initial_turn.halfmove_clock, initial_turn.fullmove_number), (5, 30));
+    assert_eq!(initial_turn.to_fen().unwrap(), fen);
+
+    let mut turns = Turns::new(initial_turn);
+    for san in ["Nf6", "Nc3", "d6"] {
+        let move_ = parse_san(&turns.current().unwrap().board, san).unwrap();
+        turns.try_push_move(&move_).unwrap();
+    }
+    assert_eq!(
+        turns.current().unwrap().to_fen().unwrap(),
+        "r1bqkb1r/ppp2ppp/2np1n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq - 0 32"
+    );
+}
+
+#[test]
+fn shredder_castling_rights() {
+    // With two rooks on the same side of the king, a file letter tells which one may castle
+    let inner_rook = Board::from_fen("4k3/8/8/8/8/8/8/R1R1K3 w C - 0 1").unwrap();
+    assert_eq!(piece(&inner_rook, "a1").model, Rook { can_castle: false });
+    assert_eq!(piece(&inner_rook, "c1").model, Rook { can_castle: true });
+    assert_eq!(inner_rook.to_fen(0, 1).unwrap(), "4k3/8/8/8/8/8/8/R1R1K3 w C - 0 1");
+
+    // Q designates the outermost rook, whatever its file
+    let outer_rook = Board::from_fen("4k3/8/8/8/8/8/8/R1R1K3 w A - 0 1").unwrap();
+    assert_eq!(piece(&outer_rook, "a1").model, Rook { can_castle: true });
+    assert_eq!(outer_rook.to_fen(0, 1).unwrap(), "4k3/8/8/8/8/8/8/R1R1K3 w Q - 0 1");
+}
+
+#[test]
+fn invalid_fen() {
+    for fen in [
+        "",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1",
+        "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN1 w KQkq - 0 1",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e3 0 1",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1",
+        "Pnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1",
+    ] {
+        assert!(Board::from_fen(fen).is_err(), "{:?}", fen);
+    }
+}
+
+#[test]
+fn unexportable_boards() {
+    // Other sizes, holes and custom promotions have no FEN
+    let closeup = Board::from_strings_strict(
+        "rnbqkbnr\npppppppp\n________\n________\n________\nPPPPPPPP\nRNBQKBNR",
+        "WWWWWWWW\n________\n________\n________\n________\n________\nbbbbbbbb",
+    )
+    .unwrap();
+    assert!(closeup.to_fen(0, 1).is_err());
+
+    let hole = Board::from_strings_strict(
+        "rnbqkbnr\npppppppp\n________\n___XX___\n___XX___\n________\nPPPPPPPP\nRNBQKBNR",
+        "WWWWWWWW\n________\n________\n___XX___\n___XX___\n________\n________\nbbbbbbbb",
+    )
+    .unwrap();
+    assert!(hole.to_fen(0, 1).is_err());
+
+    let no_promotions = Board::from_notation("4k3/8/8/8/8/8/8/4K3 8/8/8/8/8/8/8/8 w -").unwrap();
+    assert!(no_promotions.to_fen(0, 1).is_err());
+}
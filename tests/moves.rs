@@ -0,0 +1,78 @@
+// Move input: resolving moves from a source, a target and a promotion choice, and the errors on invalid ones
+
+use chess2::board::PieceModel::*;
+use chess2::board::*;
+use chess2::logic::*;
+
+const STANDARD_PROMOTIONS: &str = "WWWWWWWW/8/8/8/8/8/8/bbbbbbbb";
+
+fn board(squares: &str) -> Board {
+    Board::from_notation(&format!("{} {} w -", squares, STANDARD_PROMOTIONS)).unwrap()
+}
+
+fn coords(x: isize, y: isize) -> Coords {
+    Coords { x, y }
+}
+
+fn resolve(board: &Board, [sx, sy, tx, ty]: [isize; 4], promotion: Option<PieceModel>) -> Result<Move, MoveError> {
+    resolve_move(board, coords(sx, sy), coords(tx, ty), promotion)
+}
+
+fn piece_at(board: &Board, coords: Coords) -> Option<Piece> {
+    match board.spaces.get(coords) {
+        Some(Space::Square { slot, .. }) => *slot,
+        _ => None,
+    }
+}
+
+#[test]
+fn resolved_moves() {
+    let board = board("k7/1P6/8/8/8/8/8/R3K3");
+    // Kinds are found by the rules
+    assert_eq!(resolve(&board, [4, 0, 2, 0], None).unwrap().kind, MoveKind::Castle { rook_coords: coords(0, 0) });
+    assert_eq!(resolve(&board, [0, 0, 0, 1], None).unwrap().kind, MoveKind::Standard);
+    assert_eq!(resolve(&board, [1, 6, 1, 7], Some(Knight)).unwrap().promotion, Some(Knight));
+    // Promoted rooks get their castling rights from the rules, whatever the choice says
+    for can_castle in [false, true] {
+        let move_ = resolve(&board, [1, 6, 1, 7], Some(Rook { can_castle })).unwrap();
+        assert_eq!(move_.promotion, Some(Rook { can_castle: true }));
+    }
+}
+
+#[test]
+fn applied_moves() {
+    let board = board("k7/1P6/8/8/8/8/8/4K3");
+
+    // A rook promotion given without castling rights plays the legal move
+    let choice = Move {
+        source: coords(1, 6),
+        target: coords(1, 7),
+        kind: MoveKind::Standard,
+        promotion: Some(Rook { can_castle: false }),
+    };
+    let next_board = try_apply_move(&board, &choice).unwrap();
+    assert_eq!(piece_at(&next_board, coords(1, 7)).unwrap().model, Rook { can_castle: true });
+    let legal_move = Move { promotion: Some(Rook { can_castle: true }), ..choice };
+    assert_eq!(next_board, get_next_board(&board, &legal_move));
+
+    // Errors are those of resolve_move
+    let illegal = Move { target: coords(1, 5), promotion: None, ..choice };
+    assert_eq!(try_apply_move(&board, &illegal), Err(MoveError::Illegal));
+}
+
+#[test]
+fn move_errors() {
+    let board = board("k5b1/1P6/8/8/4X3/8/P7/K7");
+
+    assert_eq!(resolve(&board, [8, 0, 7, 0], None), Err(MoveError::OutOfBounds { coords: coords(8, 0) }));
+    assert_eq!(resolve(&board, [0, 1, 0, -1], None), Err(MoveError::OutOfBounds { coords: coords(0, -1) }));
+    assert_eq!(resolve(&board, [4, 3, 4, 4], None), Err(MoveError::NoSquare { coords: coords(4, 3) }));
+    assert_eq!(resolve(&board, [0, 0, 4, 3], None), Err(MoveError::NoSquare { coords: coords(4, 3) }));
+    assert_eq!(resolve(&board, [3, 3, 3, 4], None), Err(MoveError::NoPiece { coords: coords(3, 3) }));
+    assert_eq!(resolve(&board, [6, 7, 5, 6], None), Err(MoveError::WrongSide { coords: coords(6, 7) }));
+    assert_eq!(resolve(&board, [0, 1, 1, 2], None), Err(MoveError::Illegal));
+    assert_eq!(resolve(&board, [1, 6, 1, 7], None), Err(MoveError::MissingPromotion));
+    assert_eq!(resolve(&board, [1, 6, 1, 7], Some(King { can_castle: false })), Err(MoveError::InvalidPromotion));
+    assert_eq!(resolve(&board, [1, 6, 1, 7], Some(Pawn { can_dash: false, just_dashed: false })), Err(MoveError::InvalidPromotion));
+    assert_eq!(resolve(&board, [0, 1, 0, 2], Some(Queen)), Err(MoveError::UnexpectedPromotion));
+}
@@ -0,0 +1,96 @@
+// Square names and move notation, including round trips through every legal move of reference positions
+
+use chess2::board::*;
+use chess2::logic::*;
+use chess2::notation::*;
+
+const STANDARD_PROMOTIONS: &str = "WWWWWWWW/8/8/8/8/8/8/bbbbbbbb";
+
+fn standard_position(squares: &str, side: char) -> Board {
+    Board::from_notation(&format!("{} {} {} -", squares, STANDARD_PROMOTIONS, side)).unwrap()
+}
+
+fn assert_round_trips(board: &Board) {
+    for move_ in compute_possible_moves(board, true).into_values().flatten() {
+        let san = move_to_san(board, &move_);
+        assert_eq!(parse_san(board, &san), Ok(move_), "parsing {}", san);
+    }
+}
+
+fn san(board: &Board, source: &str, target: &str) -> String {
+    let move_ = resolve_move(board, parse_square(source).unwrap(), parse_square(target).unwrap(), None).unwrap();
+    move_to_san(board, &move_)
+}
+
+fn notation_ambiguous(san: &str) -> NotationError {
+    NotationError::AmbiguousMove { san: String::from(san) }
+}
+
+#[test]
+fn square_names() {
+    for (x, name) in [(0, "a"), (7, "h"), (25, "z"), (26, "aa"), (51, "az"), (52, "ba"), (701, "zz"), (702, "aaa")] {
+        assert_eq!(file_name(x), name);
+        assert_eq!(parse_square(&format!("{}1", name)), Some(Coords { x, y: 0 }));
+    }
+    assert_eq!(square_name(Coords { x: 27, y: 11 }), "ab12");
+    assert_eq!(parse_square("e0"), None);
+    assert_eq!(parse_square("4e"), None);
+    assert_eq!(parse_square("E4"), None);
+}
+
+#[test]
+fn standard_moves() {
+    let start = standard_position("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR", 'w');
+    assert_eq!(san(&start, "g1", "f3"), "Nf3");
+    assert_eq!(san(&start, "e2", "e4"), "e4");
+
+    // Knights of the same file and rank, rooks on the same file
+    let board = standard_position("4k3/8/8/8/8/R'2N1N2/8/R'3K3", 'w');
+    assert_eq!(san(&board, "d3", "e5"), "Nde5");
+    assert_eq!(san(&board, "a3", "a2"), "R3a2");
+    assert_eq!(parse_san(&board, "Ne5"), Err(notation_ambiguous("Ne5")));
+    assert!(parse_san(&board, "Nd3e5").is_ok());
+
+    let board = standard_position("r3k2r/1P'6/8/2p!P'4/8/8/8/R3K2R", 'w');
+    assert_eq!(san(&board, "e1", "g1"), "O-O");
+    assert_eq!(san(&board, "e1", "c1"), "O-O-O");
+    assert_eq!(san(&board, "d5", "c6"), "dxc6");
+    assert_eq!(parse_san(&board, "0-0-0"), parse_san(&board, "O-O-O"));
+
+    let promotion = parse_san(&board, "bxa8=Q+").unwrap();
+    assert_eq!(promotion.promotion, Some(PieceModel::Queen));
+    assert_eq!(move_to_san(&board, &promotion), "bxa8=Q+");
+
+    let mate = standard_position("k7/8/1K6/8/8/8/8/7R", 'w');
+    assert_eq!(san(&mate, "h1", "h8"), "Rh8#");
+    assert_eq!(parse_san(&mate, "Rh8"), parse_san(&mate, "Rh8#!"));
+    assert_eq!(parse_san(&mate, "Rh9"), Err(NotationError::NoMatchingMove { san: String::from("Rh9") }));
+}
+
+#[test]
+fn round_trips() {
+    assert_round_trips(&standard_position("r3k2r/p1ppqpb1/bn2p'np'1/3P'N3/1p'2P'3/2N2Q1p'/PPPBBPPP/R3K2R", 'w'));
+    assert_round_trips(&standard_position("r'nbq1k'1r'/pp1P'bppp/2p'5/8/2B5/8/PPP1NnPP/RNBQK2R", 'w'));
+    assert_round_trips(&standard_position("8/2p5/3p'4/K'P'5r'/1R'3p'1k'/8/4P1P1/8", 'w'));
+
+    // Two kings castling the same way on the double layout
+    let double = Board::from_strings_strict(
+        "r___k__rXXr___k__r\n________XX________\n________XX________\n________XX________\n________XX________\n________XX________\n________XX________\nR___K__RXXR___K__R",
+        "WWWWWWWWXXWWWWWWWW\n________XX________\n________XX________\n________XX________\n________XX________\n________XX________\n________XX________\nbbbbbbbbXXbbbbbbbb",
+    )
+    .unwrap();
+    assert_eq!(san(&double, "e1", "g1"), "e1O-O");
+    assert_eq!(san(&double, "o1", "q1"), "o1O-O");
+    assert_round_trips(&double);
+
+    // Files past z
+    let wide = Board::from_notation("k29/30/30/K28R 30/30/30/30 w -").unwrap();
+    assert_eq!(san(&wide, "ad1", "ad4"), "Rad4+");
+    assert_round_trips(&wide);
+
+    // A file disambiguation would merge with the target's file: Qa1 to d4 and Qad1 to ad4 would both be Qad4
+    let colliding = Board::from_notation("k29/30/K29/Q2Q25Q 30/30/30/30 w -").unwrap();
+    assert_eq!(san(&colliding, "a1", "d4"), "Qa1d4#");
+    assert_eq!(san(&colliding, "ad1", "ad4"), "Qad4#");
+    assert_round_trips(&colliding);
+}
@@ -0,0 +1,133 @@
+// Game outcomes: checkmate, stalemate and draws, including on multi-king and holed boards
+
+use chess2::board::*;
+use chess2::logic::*;
+use chess2::turns::*;
+
+const NO_PROMOTIONS: &str = "8/8/8/8/8/8/8/8";
+
+fn board(squares: &str, side: char) -> Board {
+    Board::from_notation(&format!("{} {} {} -", squares, NO_PROMOTIONS, side)).unwrap()
+}
+
+fn outcome(squares: &str, side: char) -> GameOutcome {
+    Turn::new(board(squares, side), Move::skip()).outcome
+}
+
+fn play(turns: &mut Turns, moves: &[[isize; 4]]) {
+    for &[sx, sy, tx, ty] in moves {
+        let current_board = &turns.current().unwrap().board;
+        let move_ = resolve_move(current_board, Coords { x: sx, y: sy }, Coords { x: tx, y: ty }, None).unwrap();
+        turns.try_push_move(&move_).unwrap();
+    }
+}
+
+const INSUFFICIENT_MATERIAL: GameOutcome = GameOutcome::Draw { reason: DrawReason::InsufficientMaterial };
+
+#[test]
+fn checkmate_and_stalemate() {
+    // Queen protected by its king next to the cornered king
+    assert_eq!(outcome("k7/1Q6/1K6/8/8/8/8/8", 'b'), GameOutcome::Checkmate { winner: Side::White });
+    // The same king left without moves but not attacked
+    assert_eq!(outcome("k7/8/1Q6/8/8/8/8/K7", 'b'), GameOutcome::Stalemate);
+    // A piece able to take the checking queen
+    assert_eq!(outcome("k7/1Q5r/1K6/8/8/8/8/8", 'b'), GameOutcome::Ongoing);
+    // Stalemate only counts the side to move
+    assert_eq!(outcome("k7/8/1Q6/8/8/8/8/K7", 'w'), GameOutcome::Ongoing);
+}
+
+#[test]
+fn multiple_kings() {
+    // A mated king loses the game even though the other king could move
+    assert_eq!(outcome("k6k/1Q6/1K6/8/8/8/8/8", 'b'), GameOutcome::Checkmate { winner: Side::White });
+    // Unless a move of the other king's side saves it
+    assert_eq!(outcome("k6k/1Q5r/1K6/8/8/8/8/8", 'b'), GameOutcome::Ongoing);
+    // A stalemated king doesn't stop the other king from moving
+    assert_eq!(outcome("k6k/8/1Q6/8/8/8/8/K7", 'b'), GameOutcome::Ongoing);
+    // Both kings without moves and neither attacked
+    assert_eq!(outcome("k6k/8/1Q4Q1/8/8/8/8/K7", 'b'), GameOutcome::Stalemate);
+}
+
+#[test]
+fn holed_boards() {
+    // Holes take away the flight squares of the cornered king
+    assert_eq!(outcome("k6R/XX6/8/8/8/8/8/K7", 'b'), GameOutcome::Checkmate { winner: Side::White });
+    assert_eq!(outcome("kX6/XX6/8/8/8/8/8/K7", 'b'), GameOutcome::Stalemate);
+    // Without the holes, the king escapes
+    assert_eq!(outcome("k6R/8/8/8/8/8/8/K7", 'b'), GameOutcome::Ongoing);
+}
+
+#[test]
+fn insufficient_material() {
+    assert_eq!(outcome("k7/8/8/8/8/8/8/K7", 'w'), INSUFFICIENT_MATERIAL);
+    // A lone knight or bishop can't mate on an 8x8 board
+    assert_eq!(outcome("k7/8/8/8/8/8/8/KN6", 'w'), INSUFFICIENT_MATERIAL);
+    assert_eq!(outcome("k7/8/8/8/8/8/8/KB6", 'w'), INSUFFICIENT_MATERIAL);
+    assert_eq!(outcome("kn6/8/8/8/8/8/8/K7", 'w'), INSUFFICIENT_MATERIAL);
+    // Any other material may mate
+    assert_eq!(outcome("k7/8/8/8/8/8/8/KR6", 'w'), GameOutcome::Ongoing);
+    assert_eq!(outcome("k7/8/8/8/8/8/8/KP6", 'w'), GameOutcome::Ongoing);
+    assert_eq!(outcome("kn6/8/8/8/8/8/8/KN6", 'w'), GameOutcome::Ongoing);
+    assert_eq!(outcome("k7/8/8/8/8/8/8/KNN5", 'w'), GameOutcome::Ongoing);
+}
+
+#[test]
+fn mating_patterns_on_holed_boards() {
+    // Holes next to the corner leave the king a single flight square, which the attacking king can cover:
+    // Kc6 and Nc7 mate a king on a8, as do Kc8 and Bd5
+    assert_eq!(outcome("kX6/X7/8/8/8/8/8/KN6", 'w'), GameOutcome::Ongoing);
+    assert_eq!(outcome("kX6/X7/8/8/8/8/8/KB6", 'w'), GameOutcome::Ongoing);
+    assert!(!is_insufficient_material(&board("kX6/X7/8/8/8/8/8/KN6", 'w')));
+
+    // Holes far from any edge don't help
+    assert_eq!(outcome("k7/8/8/3XX3/3XX3/8/8/KN6", 'w'), INSUFFICIENT_MATERIAL);
+}
+
+#[test]
+fn multiple_kings_material() {
+    // Kings alone never mate, however many there are
+    assert_eq!(outcome("k6k/8/8/8/8/8/8/K6K", 'w'), INSUFFICIENT_MATERIAL);
+    // A minor piece is only known to be harmless against a lone king, with a single king of its own
+    assert_eq!(outcome("k6k/8/8/8/8/8/8/KN6", 'w'), GameOutcome::Ongoing);
+    assert_eq!(outcome("k7/8/8/8/8/8/8/KN5K", 'w'), GameOutcome::Ongoing);
+}
+
+#[test]
+fn fifty_move_rule() {
+    let mut initial_turn = Turn::new(board("k7/8/8/8/8/8/8/KR6", 'w'), Move::skip());
+    initial_turn.halfmove_clock = 98;
+    let mut turns = Turns::new(initial_turn);
+
+    play(&mut turns, &[[1, 0, 1, 1]]);
+    assert_eq!(turns.current().unwrap().outcome, GameOutcome::Ongoing);
+    play(&mut turns, &[[0, 7, 0, 6]]);
+    assert_eq!(turns.current().unwrap().outcome, GameOutcome::Draw { reason: DrawReason::FiftyMoveRule });
+
+    // A pawn move resets the count
+    let mut initial_turn = Turn::new(board("k7/8/8/8/8/8/P7/K7", 'w'), Move::skip());
+    initial_turn.halfmove_clock = 99;
+    let mut turns = Turns::new(initial_turn);
+    play(&mut turns, &[[0, 1, 0, 2]]);
+    assert_eq!(turns.current().unwrap().outcome, GameOutcome::Ongoing);
+    assert_eq!(turns.current().unwrap().halfmove_clock, 0);
+
+    // Checkmate on the hundredth halfmove still wins
+    let mut initial_turn = Turn::new(board("k7/8/1K6/8/8/8/8/7R", 'w'), Move::skip());
+    initial_turn.halfmove_clock = 99;
+    let mut turns = Turns::new(initial_turn);
+    play(&mut turns, &[[7, 0, 7, 7]]);
+    assert_eq!(turns.current().unwrap().outcome, GameOutcome::Checkmate { winner: Side::White });
+}
+
+#[test]
+fn threefold_repetition() {
+    // Pieces that already lost their castling rights, so that moving them back restores the same position
+    let mut turns = Turns::new(Turn::new(board("k'7/8/8/8/8/8/8/K'R'6", 'w'), Move::skip()));
+    let rook_and_king_shuffle = [[1, 0, 1, 1], [0, 7, 0, 6], [1, 1, 1, 0], [0, 6, 0, 7]];
+
+    // The initial position comes back a second time, then a third
+    play(&mut turns, &rook_and_king_shuffle);
+    assert_eq!(turns.current().unwrap().outcome, GameOutcome::Ongoing);
+    play(&mut turns, &rook_and_king_shuffle);
+    assert_eq!(turns.current().unwrap().outcome, GameOutcome::Draw { reason: DrawReason::ThreefoldRepetition });
+}
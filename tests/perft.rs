@@ -0,0 +1,355 @@
+// Perft regression suite: leaf counts of the legal move tree of reference positions
+// Standard chess positions are checked against their published counts, and any difference is explained next to it.
+// Shipped layouts are checked against counts recorded from this engine, so that any rules change shows up here
+// The same move trees are used to check the state make_move maintains incrementally
+
+use chess2::board::*;
+use chess2::logic::*;
+
+const STANDARD_PROMOTIONS: &str = "WWWWWWWW/8/8/8/8/8/8/bbbbbbbb";
+
+fn assert_perft(board: &Board, expected: &[u64]) {
+    for (depth, &nodes) in (1..).zip(expected) {
+        assert_eq!(perft(board, depth), nodes, "perft at depth {}", depth);
+    }
+}
+
+fn standard_position(squares: &str) -> Board {
+    Board::from_notation(&format!("{} {} w -", squares, STANDARD_PROMOTIONS)).unwrap()
+}
+
+fn layout(pieces: &str, promotions: &str) -> Board {
+    Board::from_strings_strict(pieces, promotions).unwrap()
+}
+
+// The layout with the knights, bishops and queens taken off its first and last rows, after the leftmost king of each side
+// went one square to the right and came back, so that its counts depend on the castling rights of moved kings
+fn king_returned(board: &Board) -> Board {
+    let mut board = board.clone();
+    let (width, height) = board.spaces.dim();
+    for x in 0..width {
+        for y in [0, height - 1] {
+            if let Space::Square { slot, .. } = &mut board.spaces[[x, y]] {
+                if matches!(slot, Some(Piece { model: PieceModel::Knight | PieceModel::Bishop | PieceModel::Queen, .. })) {
+                    *slot = None;
+                }
+            }
+        }
+    }
+    board.hash = board.compute_hash();
+
+    for x_dir in [1, 1, -1, -1] {
+        let king = board
+            .spaces
+            .indexed_iter()
+            .find_map(|((x, y), space)| match space {
+                Space::Square { slot: Some(Piece { model: PieceModel::King { .. }, side }), .. } if *side == board.side => {
+                    Some(Coords { x: x as isize, y: y as isize })
+                }
+                _ => None,
+            })
+            .unwrap();
+        let move_ = resolve_move(&board, king, Coords { x: king.x + x_dir, ..king }, None).unwrap();
+        board = get_next_board(&board, &move_);
+    }
+    board
+}
+
+#[test]
+fn standard_start() {
+    let board = layout(
+        "
+        rnbqkbnr
+        pppppppp
+        ________
+        ________
+        ________
+        ________
+        PPPPPPPP
+        RNBQKBNR",
+        "
+        WWWWWWWW
+        ________
+        ________
+        ________
+        ________
+        ________
+        ________
+        bbbbbbbb",
+    );
+    assert_perft(&board, &[20, 400, 8902, 197281]);
+    assert_perft(&king_returned(&board), &[23, 529, 12035]);
+}
+
+#[test]
+fn standard_en_passant_and_pins() {
+    // "Position 3" of the chess programming wiki
+    let board = standard_position("8/2p5/3p'4/K'P'5r'/1R'3p'1k'/8/4P1P1/8");
+    assert_perft(&board, &[14, 191, 2812, 43238]);
+}
+
+#[test]
+fn standard_promotions() {
+    // "Position 5" of the chess programming wiki
+    let board = standard_position("r'nbq1k'1r'/pp1P'bppp/2p'5/8/2B5/8/PPP1NnPP/RNBQK2R");
+    assert_perft(&board, &[44, 1486, 62379]);
+}
+
+// Perft under the standard castling rule, where only the squares the king crosses must be safe:
+// castles that chess2 rejects because of an attacked square on the rook's side are added back
+fn perft_standard_castling(board: &mut Board, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let mover = board.side;
+    let mut moves: Vec<Move> = compute_possible_moves(board, true).into_values().flatten().collect();
+    let castles: Vec<Move> = compute_possible_moves(board, false)
+        .into_values()
+        .flatten()
+        .filter(|move_| matches!(move_.kind, MoveKind::Castle { .. }) && !moves.contains(move_))
+        .collect();
+
+    for castle in castles {
+        let x_dir = (castle.target.x - castle.source.x).signum();
+        let crossed = Coords { x: castle.source.x + x_dir, y: castle.source.y };
+        let king_path_safe = [Move { target: crossed, kind: MoveKind::Standard, ..castle }, castle]
+            .iter()
+            .all(|step| {
+                let undo = make_move(board, step);
+                let safe = !is_square_attacked(board, step.target, mover.other());
+                unmake_move(board, undo);
+                safe
+            });
+        if !is_in_check(board) && king_path_safe {
+            moves.push(castle);
+        }
+    }
+
+    moves
+        .iter()
+        .map(|move_| {
+            let undo = make_move(board, move_);
+            let nodes = perft_standard_castling(board, depth - 1);
+            unmake_move(board, undo);
+            nodes
+        })
+        .sum()
+}
+
+#[test]
+fn castling_through_attacked_rook_path() {
+    // "Kiwipete": chess2 forbids castling when any square between the king and the rook is attacked,
+    // not only those the king crosses. This only removes castles, hence the counts below the published 2039 and 97862
+    let board = standard_position("r3k2r/p1ppqpb1/bn2p'np'1/3P'N3/1p'2P'3/2N2Q1p'/PPPBBPPP/R3K2R");
+    assert_perft(&board, &[48, 2038, 97814]);
+
+    // Adding back the castles only forbidden by that rule gives the published counts, so it explains the whole difference
+    let counts: Vec<u64> = (1..=3).map(|depth| perft_standard_castling(&mut board.clone(), depth)).collect();
+    assert_eq!(counts, [48, 2039, 97862]);
+}
+
+#[test]
+fn moved_king_or_rook_cannot_castle() {
+    // The king and the rook each go away and come back: neither may castle afterwards
+    let board = standard_position("r3k2r/8/8/8/8/8/8/R3K2R");
+    let castles = |board: &Board| {
+        compute_possible_moves(board, true)
+            .into_values()
+            .flatten()
+            .filter(|move_| matches!(move_.kind, MoveKind::Castle { .. }))
+            .count()
+    };
+    assert_eq!(castles(&board), 2);
+
+    // Moves as source x, source y, target x, target y
+    let play = |board: &Board, moves: &[[isize; 4]]| {
+        moves.iter().fold(board.clone(), |board, &[sx, sy, tx, ty]| {
+            let move_ = resolve_move(&board, Coords { x: sx, y: sy }, Coords { x: tx, y: ty }, None).unwrap();
+            get_next_board(&board, &move_)
+        })
+    };
+    let king_moved = play(&board, &[[4, 0, 4, 1], [0, 7, 0, 6], [4, 1, 4, 0], [0, 6, 0, 7]]);
+    assert_eq!(castles(&king_moved), 0);
+
+    let rook_moved = play(&board, &[[7, 0, 7, 1], [0, 7, 0, 6], [7, 1, 7, 0], [0, 6, 0, 7]]);
+    assert_eq!(castles(&rook_moved), 1);
+}
+
+#[test]
+fn hole_layout() {
+    let board = layout(
+        "
+        rnbqkbnr
+        pppppppp
+        ________
+        ___XX___
+        ___XX___
+        ________
+        PPPPPPPP
+        RNBQKBNR",
+        "
+        WWWWWWWW
+        ________
+        ________
+        ___XX___
+        ___XX___
+        ________
+        ________
+        bbbbbbbb",
+    );
+    assert_perft(&board, &[18, 324, 6246, 120001]);
+    assert_perft(&king_returned(&board), &[21, 441, 9137]);
+}
+
+#[test]
+fn closeup_layout() {
+    let board = layout(
+        "
+        rnbqkbnr
+        pppppppp
+        ________
+        ________
+        ________
+        PPPPPPPP
+        RNBQKBNR",
+        "
+        WWWWWWWW
+        ________
+        ________
+        ________
+        ________
+        ________
+        bbbbbbbb",
+    );
+    assert_perft(&board, &[20, 392, 8590, 184450]);
+    assert_perft(&king_returned(&board), &[23, 521, 11704]);
+}
+
+#[test]
+fn marathon_layout() {
+    let board = layout(
+        "
+        rnbqkbnr
+        pppppppp
+        ________
+        ________
+        ________
+        ________
+        ________
+        PPPPPPPP
+        RNBQKBNR",
+        "
+        WWWWWWWW
+        ________
+        ________
+        ________
+        ________
+        ________
+        ________
+        ________
+        bbbbbbbb",
+    );
+    assert_perft(&board, &[20, 400, 8900, 197801]);
+    assert_perft(&king_returned(&board), &[23, 529, 12029]);
+}
+
+#[test]
+fn double_layout() {
+    let board = layout(
+        "
+        rnbqkbnrXXrnbqkbnr
+        ppppppppXXpppppppp
+        ________XX________
+        ________XX________
+        ________XX________
+        ________XX________
+        PPPPPPPPXXPPPPPPPP
+        RNBQKBNRXXRNBQKBNR",
+        "
+        WWWWWWWWXXWWWWWWWW
+        ________XX________
+        ________XX________
+        ________XX________
+        ________XX________
+        ________XX________
+        ________XX________
+        bbbbbbbbXXbbbbbbbb",
+    );
+    assert_perft(&board, &[40, 1600, 67604]);
+    assert_perft(&king_returned(&board), &[48, 2304, 109500]);
+}
+
+#[test]
+fn secret_passage_layout() {
+    let board = layout(
+        "
+        __rnbqkbnr__
+        _XppppppppX_
+        _X________X_
+        _X________X_
+        _X________X_
+        _X________X_
+        _XPPPPPPPPX_
+        __RNBQKBNR__",
+        "
+        _WWWWWWWWWW_
+        _X________X_
+        _X________X_
+        _X________X_
+        _X________X_
+        _X________X_
+        _X________X_
+        _bbbbbbbbbb_",
+    );
+    assert_perft(&board, &[24, 576, 15386, 409889]);
+    assert_perft(&king_returned(&board), &[27, 729, 19905]);
+}
+
+#[test]
+fn divide_sums_to_perft() {
+    let board = standard_position("r3k2r/p1ppqpb1/bn2p'np'1/3P'N3/1p'2P'3/2N2Q1p'/PPPBBPPP/R3K2R");
+    let divide = perft_divide(&board, 2);
+
+    assert_eq!(divide.len(), 48);
+    assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), perft(&board, 2));
+}
+
+// Walks the move tree, checking the incremental hash against a full computation at every node
+fn assert_hash_consistent(board: &mut Board, depth: u32) {
+    assert_eq!(board.hash, board.compute_hash(), "hash of {}", board.to_notation());
+    if depth == 0 {
+        return;
+    }
+
+    let moves: Vec<Move> = compute_possible_moves(board, true).into_values().flatten().collect();
+    for move_ in &moves {
+        let undo = make_move(board, move_);
+        assert_hash_consistent(board, depth - 1);
+        unmake_move(board, undo);
+    }
+}
+
+#[test]
+fn incremental_hash() {
+    // Castling, dashes and en passant, promotions
+    for squares in [
+        "r3k2r/p1ppqpb1/bn2p'np'1/3P'N3/1p'2P'3/2N2Q1p'/PPPBBPPP/R3K2R",
+        "8/2p5/3p'4/K'P'5r'/1R'3p'1k'/8/4P1P1/8",
+        "r'nbq1k'1r'/pp1P'bppp/2p'5/8/2B5/8/PPP1NnPP/RNBQK2R",
+    ] {
+        let mut board = standard_position(squares);
+        assert_hash_consistent(&mut board, 3);
+        assert_eq!(board, standard_position(squares));
+    }
+}
+
+#[test]
+fn hash_covers_side_and_flags() {
+    let board = standard_position("r3k2r/8/8/8/8/8/8/R3K2R");
+    let black_to_move = Board::from_notation(&format!("r3k2r/8/8/8/8/8/8/R3K2R {} b -", STANDARD_PROMOTIONS)).unwrap();
+    let no_castling = standard_position("r3k2r/8/8/8/8/8/8/R3K'2R");
+
+    assert_ne!(board.hash, black_to_move.hash);
+    assert_ne!(board.hash, no_castling.hash);
+}
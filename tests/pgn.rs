@@ -0,0 +1,133 @@
+// Game records: PGN export of game trees and import back into identical trees
+
+use chess2::board::*;
+use chess2::logic::*;
+use chess2::notation::*;
+use chess2::pgn::*;
+use chess2::turns::*;
+
+fn turns_from(board: Board) -> Turns {
+    Turns::new(Turn::new(board, Move::skip()))
+}
+
+fn play(turns: &mut Turns, sans: &[&str]) {
+    for san in sans {
+        let move_ = parse_san(&turns.current().unwrap().board, san).unwrap();
+        turns.try_push_move(&move_).unwrap();
+    }
+}
+
+// Compares two trees turn by turn, following children in order
+fn assert_same_tree(a: &Turns, b: &Turns) {
+    fn compare(a: &Turns, a_id: TurnId, b: &Turns, b_id: TurnId) {
+        let (a_turn, b_turn) = (a.get(a_id).unwrap(), b.get(b_id).unwrap());
+        assert_eq!(a_turn.previous_move, b_turn.previous_move);
+        assert_eq!(a_turn.board, b_turn.board);
+        assert_eq!(a_turn.outcome, b_turn.outcome);
+        assert_eq!(a.children(a_id).len(), b.children(b_id).len());
+        for (&a_child, &b_child) in a.children(a_id).iter().zip(b.children(b_id)) {
+            compare(a, a_child, b, b_child);
+        }
+    }
+    assert_eq!(a.len(), b.len());
+    compare(a, 0, b, 0);
+}
+
+#[test]
+fn standard_game() {
+    // The app's standard layout needs no layout tag
+    let standard = Board::from_strings_strict(
+        "rnbqkbnr\npppppppp\n________\n________\n________\n________\nPPPPPPPP\nRNBQKBNR",
+        "WWWWWWWW\n________\n________\n________\n________\n________\n________\nbbbbbbbb",
+    )
+    .unwrap();
+    assert_eq!(standard, Board::from_notation(STANDARD_LAYOUT).unwrap());
+
+    let mut turns = turns_from(standard);
+    play(&mut turns, &["f3", "e5", "g4", "Qh4#"]);
+
+    let pgn = write_pgn(&turns);
+    assert!(pgn.contains("[Result \"0-1\"]"));
+    assert!(!pgn.contains(LAYOUT_TAG));
+    assert!(pgn.ends_with("1. f3 e5 2. g4 Qh4# 0-1\n"));
+
+    assert_same_tree(&read_pgn(&pgn).unwrap(), &turns);
+}
+
+#[test]
+fn variations() {
+    let mut turns = turns_from(Board::from_notation(STANDARD_LAYOUT).unwrap());
+    play(&mut turns, &["e4", "e5", "Nf3"]);
+    turns.go_to(turns.line(turns.current_id())[1]);
+    play(&mut turns, &["c5", "Nf3", "d6"]);
+    turns.go_to(turns.line(turns.current_id())[3]);
+    play(&mut turns, &["Nc6"]);
+
+    let pgn = write_pgn(&turns);
+    assert!(pgn.ends_with("1. e4 e5 (1... c5 2. Nf3 d6 (2... Nc6)) 2. Nf3 *\n"), "{}", pgn);
+    assert_same_tree(&read_pgn(&pgn).unwrap(), &turns);
+}
+
+#[test]
+fn custom_layout() {
+    let board = Board::from_strings_strict(
+        "__rnbqkbnr__\n_XppppppppX_\n_X________X_\n_X________X_\n_X________X_\n_X________X_\n_XPPPPPPPPX_\n__RNBQKBNR__",
+        "_WWWWWWWWWW_\n_X________X_\n_X________X_\n_X________X_\n_X________X_\n_X________X_\n_X________X_\n_bbbbbbbbbb_",
+    )
+    .unwrap();
+    let mut turns = turns_from(board.clone());
+    play(&mut turns, &["Nh3", "Nj6", "Ni1", "Ni8"]);
+
+    let pgn = write_pgn(&turns);
+    assert!(pgn.contains(&format!("[{} \"{}\"]", LAYOUT_TAG, board.to_notation())));
+    // Boards that FEN can't describe only get the layout tag
+    assert!(!pgn.contains(FEN_TAG));
+    assert_same_tree(&read_pgn(&pgn).unwrap(), &turns);
+}
+
+#[test]
+fn fen_tags() {
+    // Starting positions that fit the standard rules are also written in FEN, clocks included
+    let fen = "r3k3/8/8/8/8/8/4P3/4K2R w Kq - 5 1";
+    let mut turns = Turns::new(Turn::from_fen(fen).unwrap());
+    play(&mut turns, &["O-O", "O-O-O"]);
+
+    let pgn = write_pgn(&turns);
+    assert!(pgn.contains(LAYOUT_TAG));
+    assert!(pgn.contains(&format!("[{} \"1\"]\n[{} \"{}\"]", SETUP_TAG, FEN_TAG, fen)));
+    let read_turns = read_pgn(&pgn).unwrap();
+    assert_same_tree(&read_turns, &turns);
+    assert_eq!(read_turns.get(0).unwrap().halfmove_clock, 5);
+
+    // Games written by other tools only have the FEN tags
+    let pgn = "[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/R3K3 b Q - 12 40\"]\n\n40... Kd7 41. O-O-O+ *\n";
+    let turns = read_pgn(pgn).unwrap();
+    assert_eq!(turns.get(0).unwrap().board, Board::from_fen("4k3/8/8/8/8/8/8/R3K3 b Q - 0 1").unwrap());
+    assert_eq!(turns.get(0).unwrap().halfmove_clock, 12);
+    assert_eq!(turns.get(0).unwrap().fullmove_number, 40);
+    assert!(matches!(turns.current().unwrap().previous_move.kind, MoveKind::Castle { .. }));
+    // Their move numbers go on from the fullmove number, and are written back the same way
+    let written = write_pgn(&turns);
+    assert!(written.contains("[FEN \"4k3/8/8/8/8/8/8/R3K3 b Q - 12 40\"]"));
+    assert!(written.contains("40... Kd7 41. O-O-O+"));
+    assert_eq!(turns.current().unwrap().to_fen().unwrap(), "8/3k4/8/8/8/8/8/2KR4 b - - 14 41");
+
+    // Unless SetUp says the game starts from the standard position
+    let pgn = "[SetUp \"0\"]\n[FEN \"4k3/8/8/8/8/8/8/R3K3 b Q - 12 40\"]\n\n1. e4 *\n";
+    assert_eq!(read_pgn(pgn).unwrap().get(0).unwrap().board, Board::from_notation(STANDARD_LAYOUT).unwrap());
+
+    assert!(matches!(read_pgn("[FEN \"8/8/8 w - - 0 1\"]\n\n*"), Err(PgnError::InvalidFen { .. })));
+}
+
+#[test]
+fn other_tools_output() {
+    // Comments, glyphs, glued move numbers and castling with zeros
+    let pgn = "[Event \"Test \\\"quoted\\\"\"]\n[Result \"*\"]\n\n1.e4 {best by test} e5 $1 2.Nf3 Nc6 3.Bc4 Bc5 ; italian\n4.0-0 *\n";
+    let turns = read_pgn(pgn).unwrap();
+    assert_eq!(turns.mainline().len(), 8);
+    assert!(matches!(turns.current().unwrap().previous_move.kind, MoveKind::Castle { .. }));
+
+    assert!(matches!(read_pgn("1. e4 e5) *"), Err(PgnError::UnbalancedVariation)));
+    assert!(matches!(read_pgn("1. e5 *"), Err(PgnError::InvalidMove { .. })));
+    assert!(matches!(read_pgn("[Event]\n1. e4"), Err(PgnError::InvalidTag { line: 1 })));
+}
@@ -0,0 +1,158 @@
+// Game tree behaviour: variations, mainline promotion, undo and redo, and history-dependent draws along a line
+
+use chess2::board::*;
+use chess2::logic::*;
+use chess2::turns::*;
+
+fn standard_turns() -> Turns {
+    let board = Board::from_notation("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR WWWWWWWW/8/8/8/8/8/8/bbbbbbbb w -").unwrap();
+    Turns::new(Turn::new(board, Move::skip()))
+}
+
+fn play(turns: &mut Turns, source: (isize, isize), target: (isize, isize)) {
+    let current_board = &turns.current().unwrap().board;
+    let move_ = resolve_move(
+        current_board,
+        Coords { x: source.0, y: source.1 },
+        Coords { x: target.0, y: target.1 },
+        None,
+    )
+    .unwrap();
+    turns.try_push_move(&move_).unwrap();
+}
+
+#[test]
+fn variations_keep_the_mainline() {
+    let mut turns = standard_turns();
+    play(&mut turns, (4, 1), (4, 3));
+    play(&mut turns, (4, 6), (4, 4));
+    let mainline_end = turns.current_id();
+
+    // Alternative reply from the first turn
+    assert!(turns.go_to(turns.line(mainline_end)[1]));
+    play(&mut turns, (2, 6), (2, 4));
+    let variation_end = turns.current_id();
+
+    assert_eq!(turns.len(), 4);
+    assert_eq!(turns.mainline(), turns.line(mainline_end));
+    assert!(!turns.is_mainline(variation_end));
+
+    // Playing an existing move goes back to its turn
+    assert!(turns.go_to(turns.line(mainline_end)[1]));
+    play(&mut turns, (4, 6), (4, 4));
+    assert_eq!(turns.current_id(), mainline_end);
+    assert_eq!(turns.len(), 4);
+}
+
+#[test]
+fn promote_variation() {
+    let mut turns = standard_turns();
+    play(&mut turns, (4, 1), (4, 3));
+    let mainline_end = turns.current_id();
+
+    assert!(turns.go_to(0));
+    play(&mut turns, (3, 1), (3, 3));
+    let variation_end = turns.current_id();
+
+    turns.promote_variation(variation_end);
+    assert_eq!(turns.mainline(), vec![0, variation_end]);
+    assert_eq!(turns.children(0), &[variation_end, mainline_end]);
+}
+
+#[test]
+fn repetitions_follow_the_line() {
+    let mut turns = standard_turns();
+    let knights_out_and_back = [((6, 0), (5, 2)), ((6, 7), (5, 5)), ((5, 2), (6, 0)), ((5, 5), (6, 7))];
+
+    for (source, target) in knights_out_and_back.iter().chain(&knights_out_and_back) {
+        play(&mut turns, *source, *target);
+    }
+    assert_eq!(
+        turns.current().unwrap().outcome,
+        GameOutcome::Draw { reason: DrawReason::ThreefoldRepetition }
+    );
+
+    // The same moves in a line branching off before the repetitions don't draw
+    assert!(turns.go_to(0));
+    play(&mut turns, (1, 0), (2, 2));
+    play(&mut turns, (1, 7), (2, 5));
+    for (source, target) in knights_out_and_back {
+        play(&mut turns, source, target);
+    }
+    assert_eq!(turns.current().unwrap().outcome, GameOutcome::Ongoing);
+}
+
+#[test]
+fn undo_and_redo() {
+    let mut turns = standard_turns();
+    play(&mut turns, (4, 1), (4, 3));
+    play(&mut turns, (4, 6), (4, 4));
+    let reply = turns.current().unwrap().previous_move;
+
+    assert_eq!(turns.undo(), Some(reply));
+    assert_eq!(turns.len(), 2);
+    assert!(turns.can_redo());
+
+    assert_eq!(turns.redo(), Some(reply));
+    assert_eq!(turns.current().unwrap().previous_move, reply);
+    assert!(!turns.can_redo());
+
+    // A new move forgets what could be redone
+    turns.undo();
+    play(&mut turns, (2, 6), (2, 4));
+    assert!(!turns.can_redo());
+    assert_eq!(turns.redo(), None);
+
+    // Neither the initial turn nor turns followed by others can be taken back
+    assert!(turns.go_to(0));
+    assert_eq!(turns.undo(), None);
+    assert!(turns.go_to(1));
+    assert_eq!(turns.undo(), None);
+}
+
+#[test]
+fn undo_in_a_variation_keeps_the_tree_consistent() {
+    let mut turns = standard_turns();
+    play(&mut turns, (4, 1), (4, 3));
+    let first_move = turns.current_id();
+    play(&mut turns, (4, 6), (4, 4));
+
+    // The mainline reply is taken back after a variation was added, so it is not the last turn stored
+    assert!(turns.go_to(first_move));
+    play(&mut turns, (2, 6), (2, 4));
+    assert!(turns.go_to(turns.children(first_move)[0]));
+    turns.undo();
+
+    assert_eq!(turns.len(), 3);
+    assert_eq!(turns.current_id(), first_move);
+    let variation = turns.children(first_move)[0];
+    assert_eq!(turns.parent(variation), Some(first_move));
+    assert_eq!(turns.line(variation), vec![0, first_move, variation]);
+}
+
+#[test]
+fn undo_keeps_turn_ids() {
+    let mut turns = standard_turns();
+    play(&mut turns, (4, 1), (4, 3));
+    let first_move = turns.current_id();
+    play(&mut turns, (4, 6), (4, 4));
+    let reply = turns.current_id();
+    assert!(turns.go_to(first_move));
+    play(&mut turns, (2, 6), (2, 4));
+    // Held while the reply before it is taken back, like the turn an AI search was started for
+    let variation = turns.current_id();
+    let variation_board = turns.current().unwrap().board.clone();
+
+    assert!(turns.go_to(reply));
+    turns.undo();
+    assert_eq!(turns.get(variation).unwrap().board, variation_board);
+    assert_eq!(turns.children(first_move), [variation]);
+
+    // The id of the removed turn designates no turn, even after new ones are played
+    assert!(turns.get(reply).is_none());
+    assert!(!turns.go_to(reply));
+    play(&mut turns, (3, 6), (3, 4));
+    assert_ne!(turns.current_id(), reply);
+    assert!(turns.get(reply).is_none());
+    assert_eq!(turns.len(), 4);
+}
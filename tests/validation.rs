@@ -0,0 +1,93 @@
+// Layout validation: unplayable and suspicious starting positions
+
+use chess2::board::*;
+use chess2::logic::*;
+use chess2::validation::*;
+
+const STANDARD_PROMOTIONS: &str = "WWWWWWWW/8/8/8/8/8/8/bbbbbbbb";
+
+fn issues(squares: &str, promotions: &str, side: char) -> Vec<LayoutIssue> {
+    validate_board(&Board::from_notation(&format!("{} {} {} -", squares, promotions, side)).unwrap())
+}
+
+#[test]
+fn standard_layout() {
+    assert_eq!(issues("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR", STANDARD_PROMOTIONS, 'w'), vec![]);
+}
+
+#[test]
+fn missing_king() {
+    let found = issues("8/8/8/8/8/8/8/KR6", STANDARD_PROMOTIONS, 'w');
+    assert_eq!(found, vec![LayoutIssue::NoKing { side: Side::Black }]);
+    assert_eq!(found[0].severity(), Severity::Warning);
+
+    assert_eq!(
+        issues("8/8/8/8/8/8/8/R6r", STANDARD_PROMOTIONS, 'w'),
+        vec![LayoutIssue::NoKing { side: Side::White }, LayoutIssue::NoKing { side: Side::Black }]
+    );
+}
+
+#[test]
+fn checks_and_finished_games() {
+    // Starting in check is allowed, with a warning
+    let found = issues("k6r/8/8/8/8/8/8/7K", STANDARD_PROMOTIONS, 'w');
+    assert_eq!(found, vec![LayoutIssue::InCheck { side: Side::White }]);
+    assert_eq!(found[0].severity(), Severity::Warning);
+
+    // The side not to move being in check would let its king be taken
+    let found = issues("k6R/8/8/8/8/8/8/K7", STANDARD_PROMOTIONS, 'w');
+    assert_eq!(found, vec![LayoutIssue::OpponentInCheck { side: Side::Black }]);
+    assert_eq!(found[0].severity(), Severity::Error);
+
+    // Mated or stalemated sides can't play at all
+    let found = issues("k7/1Q6/1K6/8/8/8/8/8", STANDARD_PROMOTIONS, 'b');
+    assert_eq!(found, vec![LayoutIssue::AlreadyOver { outcome: GameOutcome::Checkmate { winner: Side::White } }]);
+    assert_eq!(found[0].severity(), Severity::Error);
+    assert_eq!(
+        issues("k7/8/1Q6/8/8/8/8/K7", STANDARD_PROMOTIONS, 'b'),
+        vec![LayoutIssue::AlreadyOver { outcome: GameOutcome::Stalemate }]
+    );
+}
+
+#[test]
+fn pawns_on_promotion_squares() {
+    let found = issues("kP6/8/8/8/8/8/8/K7", STANDARD_PROMOTIONS, 'w');
+    assert_eq!(found, vec![LayoutIssue::PawnOnPromotionSquare { coords: Coords { x: 1, y: 7 }, side: Side::White }]);
+    assert_eq!(found[0].severity(), Severity::Error);
+
+    // Only the pawn's own promotion squares count
+    assert_eq!(issues("kp6/8/8/8/8/8/8/K7", STANDARD_PROMOTIONS, 'w'), vec![]);
+    assert_eq!(
+        issues("k7/8/8/8/8/8/8/Kp6", STANDARD_PROMOTIONS, 'w'),
+        vec![LayoutIssue::PawnOnPromotionSquare { coords: Coords { x: 1, y: 0 }, side: Side::Black }]
+    );
+}
+
+#[test]
+fn unreachable_promotion_zones() {
+    // Without any promotion square
+    let found = issues("k7/8/8/8/8/8/P7/K7", "8/8/8/8/8/8/8/8", 'w');
+    assert_eq!(found, vec![LayoutIssue::PawnCannotPromote { coords: Coords { x: 0, y: 1 }, side: Side::White }]);
+    assert_eq!(found[0].severity(), Severity::Warning);
+
+    // Holes blocking the squares in front of the pawn and both diagonals
+    assert!(issues("k7/8/8/8/8/XXX5/P7/K7", STANDARD_PROMOTIONS, 'w')
+        .contains(&LayoutIssue::PawnCannotPromote { coords: Coords { x: 0, y: 1 }, side: Side::White }));
+
+    // Captures may take a pawn around a hole
+    assert_eq!(issues("k7/8/8/8/8/X7/P7/K7", STANDARD_PROMOTIONS, 'w'), vec![]);
+}
+
+#[test]
+fn disconnected_regions() {
+    let found = issues("k7/8/8/8/XXXXXXXX/8/8/KR6", STANDARD_PROMOTIONS, 'w');
+    assert_eq!(found, vec![LayoutIssue::DisconnectedRegions { count: 2 }]);
+    assert_eq!(found[0].severity(), Severity::Warning);
+
+    // Squares touching by a corner are connected
+    assert_eq!(issues("k7/8/8/8/XXXXXXX1/XXXXXXX1/8/KR6", STANDARD_PROMOTIONS, 'w'), vec![]);
+    assert_eq!(
+        issues("k2X2k1/3X4/3X4/3X4/3X4/3X4/3X4/KR1X3K", "WWW1WWWW/8/8/8/8/8/8/bbb1bbbb", 'w'),
+        vec![LayoutIssue::DisconnectedRegions { count: 2 }]
+    );
+}